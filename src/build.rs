@@ -0,0 +1,107 @@
+use crate::bundle::{BuildArtifact, BundleEvent, Settings};
+use crate::Error;
+
+use std::env;
+use std::ffi::OsString;
+use std::io;
+use std::process;
+
+/// Returns the `cargo build` command which builds the binaries for all of the
+/// given packages.  The packages share all build options other than the
+/// package name.
+fn cargo_build_command(all_settings: &[Settings]) -> Option<process::Command> {
+    let settings = all_settings.first()?;
+
+    let mut cargo =
+        process::Command::new(env::var_os("CARGO").unwrap_or_else(|| OsString::from("cargo")));
+    cargo.arg("build");
+    cargo.arg("--message-format=json-render-diagnostics");
+
+    for package in all_settings {
+        cargo.arg(format!("--package={}", package.package_name()));
+    }
+
+    if let Some(triple) = settings.target_triple() {
+        cargo.arg(format!("--target={triple}"));
+    }
+
+    if let Some(features) = settings.features() {
+        cargo.arg(format!("--features={features}"));
+    }
+
+    match settings.build_artifact() {
+        BuildArtifact::Main => {}
+        BuildArtifact::Bin(name) => {
+            cargo.arg(format!("--bin={name}"));
+        }
+        BuildArtifact::Example(name) => {
+            cargo.arg(format!("--example={name}"));
+        }
+    }
+
+    match settings.build_profile() {
+        "dev" => {}
+        "release" => {
+            cargo.arg("--release");
+        }
+        custom => {
+            cargo.arg("--profile");
+            cargo.arg(custom);
+        }
+    }
+
+    if settings.all_features() {
+        cargo.arg("--all-features");
+    }
+
+    if settings.no_default_features() {
+        cargo.arg("--no-default-features");
+    }
+
+    Some(cargo)
+}
+
+/// Builds the binaries for all of the given packages with a single `cargo
+/// build` invocation.  The path of each binary is taken from the
+/// `compiler-artifact` messages that cargo prints.  `on_event` is called
+/// with `BundleEvent::BuildStarted` before the build begins.
+pub fn build_project_if_unbuilt<F>(
+    all_settings: &mut [Settings],
+    on_event: &mut F,
+) -> Result<(), Error>
+where
+    F: FnMut(BundleEvent) -> Result<(), Error>,
+{
+    let mut cargo = match cargo_build_command(all_settings) {
+        Some(cargo) => cargo,
+        None => return Ok(()),
+    };
+
+    let packages: Vec<&str> = all_settings.iter().map(|s| s.package_name()).collect();
+    on_event(BundleEvent::BuildStarted {
+        packages: &packages,
+        target: all_settings[0].target_triple(),
+        profile: all_settings[0].build_profile(),
+    })?;
+
+    let mut child = cargo.stdout(process::Stdio::piped()).spawn()?;
+    let stdout = io::BufReader::new(child.stdout.take().unwrap());
+    for message in cargo_metadata::Message::parse_stream(stdout) {
+        if let cargo_metadata::Message::CompilerArtifact(artifact) = message? {
+            if let Some(ref executable) = artifact.executable {
+                for settings in all_settings.iter_mut() {
+                    if settings.is_built_artifact(&artifact) {
+                        settings.set_built_executable(executable.clone().into_std_path_buf());
+                    }
+                }
+            }
+        }
+    }
+    let status = child.wait()?;
+
+    if !status.success() {
+        return Err(Error::BuildFailed(status));
+    }
+
+    Ok(())
+}
@@ -7,53 +7,67 @@
 //
 // See https://developer.apple.com/go/?id=bundle-structure for a full
 // explanation.
+//
+// For distribution, the app bundle is also archived into an `.ipa` file, which
+// is a zip archive with the bundle stored under a top-level `Payload`
+// directory:
+//
+// Foobar.ipa
+//     Payload/
+//         Foobar.app/
+//             ...
 
-use super::common;
-use crate::{ResultExt, Settings};
+use crate::bundle;
+use crate::file;
+use crate::image;
+use crate::terminal;
+use crate::Error;
 
-use image::png::{PNGDecoder, PNGEncoder};
-use image::{self, GenericImage, ImageDecoder};
+use ::image::png::{PNGDecoder, PNGEncoder};
+use ::image::{GenericImage, ImageDecoder};
 use std::collections::BTreeSet;
 use std::ffi::OsStr;
 use std::fs::{self, File};
-use std::io::Write;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
 
-pub fn bundle_project(settings: &Settings) -> crate::Result<Vec<PathBuf>> {
-    common::print_warning("iOS bundle support is still experimental.")?;
+pub fn bundle_project(settings: &bundle::Settings) -> Result<Vec<PathBuf>, Error> {
+    terminal::print_warning("iOS bundle support is still experimental.")?;
 
     let app_bundle_name = format!("{}.app", settings.bundle_name());
-    common::print_bundling(&app_bundle_name)?;
-    let bundle_dir = settings
-        .project_out_directory()
-        .join("bundle/ios")
-        .join(&app_bundle_name);
+    terminal::print_bundling(&app_bundle_name)?;
+    let base_dir = settings.project_out_directory().join("bundle/ios");
+    let bundle_dir = base_dir.join(&app_bundle_name);
     if bundle_dir.exists() {
-        fs::remove_dir_all(&bundle_dir)
-            .chain_err(|| format!("Failed to remove old {app_bundle_name}"))?;
+        fs::remove_dir_all(&bundle_dir)?;
     }
-    fs::create_dir_all(&bundle_dir)
-        .chain_err(|| format!("Failed to create bundle directory at {bundle_dir:?}"))?;
+    fs::create_dir_all(&bundle_dir)?;
 
     for src in settings.resource_files() {
         let src = src?;
-        let dest = bundle_dir.join(common::resource_relpath(&src));
-        common::copy_file(&src, &dest)
-            .chain_err(|| format!("Failed to copy resource file {src:?}"))?;
+        let dest = bundle_dir.join(file::resource_relpath(&src));
+        file::copy(&src, &dest)?;
     }
 
-    let icon_filenames =
-        generate_icon_files(&bundle_dir, settings).chain_err(|| "Failed to create app icons")?;
-    generate_info_plist(&bundle_dir, settings, &icon_filenames)
-        .chain_err(|| "Failed to create Info.plist")?;
+    let icon_filenames = generate_icon_files(&bundle_dir, settings)?;
+    generate_info_plist(&bundle_dir, settings, &icon_filenames)?;
     let bin_path = bundle_dir.join(settings.binary_name());
-    common::copy_file(settings.binary_path(), &bin_path)
-        .chain_err(|| format!("Failed to copy binary from {:?}", settings.binary_path()))?;
-    Ok(vec![bundle_dir])
+    file::copy(settings.binary_path(), &bin_path)?;
+
+    let ipa_name = format!("{}.ipa", settings.bundle_name());
+    terminal::print_bundling(&ipa_name)?;
+    let ipa_path = base_dir.join(&ipa_name);
+    create_ipa(&bundle_dir, &ipa_path, settings)?;
+
+    Ok(vec![bundle_dir, ipa_path])
 }
 
 /// Generate the icon files and store them under the `bundle_dir`.
-fn generate_icon_files(bundle_dir: &Path, settings: &Settings) -> crate::Result<Vec<String>> {
+fn generate_icon_files(
+    bundle_dir: &Path,
+    settings: &bundle::Settings,
+) -> Result<Vec<String>, Error> {
     let mut filenames = Vec::new();
     {
         let mut get_dest_path = |width: u32, height: u32, is_retina: bool| {
@@ -76,11 +90,11 @@ fn generate_icon_files(bundle_dir: &Path, settings: &Settings) -> crate::Result<
             }
             let mut decoder = PNGDecoder::new(File::open(&icon_path)?);
             let (width, height) = decoder.dimensions()?;
-            let is_retina = common::is_retina(&icon_path);
+            let is_retina = image::is_retina(&icon_path);
             if !sizes.contains(&(width, height, is_retina)) {
                 sizes.insert((width, height, is_retina));
                 let dest_path = get_dest_path(width, height, is_retina);
-                common::copy_file(&icon_path, &dest_path)?;
+                file::copy(&icon_path, &dest_path)?;
             }
         }
         // Fall back to non-PNG files for any missing sizes.
@@ -102,13 +116,13 @@ fn generate_icon_files(bundle_dir: &Path, settings: &Settings) -> crate::Result<
                     }
                 }
             } else {
-                let icon = image::open(&icon_path)?;
+                let icon = ::image::open(&icon_path)?;
                 let (width, height) = icon.dimensions();
-                let is_retina = common::is_retina(&icon_path);
+                let is_retina = image::is_retina(&icon_path);
                 if !sizes.contains(&(width, height, is_retina)) {
                     sizes.insert((width, height, is_retina));
                     let dest_path = get_dest_path(width, height, is_retina);
-                    let encoder = PNGEncoder::new(common::create_file(&dest_path)?);
+                    let encoder = PNGEncoder::new(file::create(&dest_path)?);
                     encoder.encode(&icon.raw_pixels(), width, height, icon.color())?;
                 }
             }
@@ -119,10 +133,10 @@ fn generate_icon_files(bundle_dir: &Path, settings: &Settings) -> crate::Result<
 
 fn generate_info_plist(
     bundle_dir: &Path,
-    settings: &Settings,
-    icon_filenames: &Vec<String>,
-) -> crate::Result<()> {
-    let file = &mut common::create_file(&bundle_dir.join("Info.plist"))?;
+    settings: &bundle::Settings,
+    icon_filenames: &[String],
+) -> Result<(), Error> {
+    let file = &mut file::create(&bundle_dir.join("Info.plist"))?;
     write!(
         file,
         "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
@@ -155,12 +169,12 @@ fn generate_info_plist(
     write!(
         file,
         "  <key>CFBundleVersion</key>\n  <string>{}</string>\n",
-        settings.version_string()
+        settings.ios_bundle_version()
     )?;
     write!(
         file,
         "  <key>CFBundleShortVersionString</key>\n  <string>{}</string>\n",
-        settings.version_string()
+        settings.apple_short_version_string()
     )?;
     write!(
         file,
@@ -179,7 +193,89 @@ fn generate_info_plist(
         writeln!(file, "  </array>")?;
     }
     write!(file, "  <key>LSRequiresIPhoneOS</key>\n  <true/>\n")?;
+    if let Some(version) = settings.ios_minimum_os_version() {
+        write!(
+            file,
+            "  <key>MinimumOSVersion</key>\n  <string>{version}</string>\n"
+        )?;
+    }
+    if !settings.ios_device_family().is_empty() {
+        write!(file, "  <key>UIDeviceFamily</key>\n  <array>\n")?;
+        for family in settings.ios_device_family() {
+            writeln!(file, "    <integer>{}</integer>", family.ui_device_family())?;
+        }
+        writeln!(file, "  </array>")?;
+    }
+    if !settings.ios_supported_orientations().is_empty() {
+        write!(
+            file,
+            "  <key>UISupportedInterfaceOrientations</key>\n  <array>\n"
+        )?;
+        for orientation in settings.ios_supported_orientations() {
+            writeln!(
+                file,
+                "    <string>{}</string>",
+                orientation.ui_orientation()
+            )?;
+        }
+        writeln!(file, "  </array>")?;
+    }
     write!(file, "</dict>\n</plist>\n")?;
     file.flush()?;
     Ok(())
 }
+
+/// Archives the app bundle at `bundle_dir` into an `.ipa` file at `ipa_path`.
+fn create_ipa(
+    bundle_dir: &Path,
+    ipa_path: &Path,
+    settings: &bundle::Settings,
+) -> Result<(), Error> {
+    let payload_dir = Path::new("Payload").join(bundle_dir.file_name().unwrap());
+    let binary_path = bundle_dir.join(settings.binary_name());
+    let mut zip = zip::ZipWriter::new(File::create(ipa_path)?);
+    let options =
+        zip::write::FileOptions::default().last_modified_time(zip_datetime(settings.timestamp()));
+    zip.add_directory("Payload/", options)?;
+    let entries = WalkDir::new(bundle_dir).sort_by(|a, b| a.file_name().cmp(b.file_name()));
+    for entry in entries {
+        let entry = entry?;
+        let rel_path = entry.path().strip_prefix(bundle_dir).unwrap();
+        let name = payload_dir.join(rel_path);
+        let name = name.to_str().ok_or_else(|| {
+            let msg = format!("Non-UTF-8 path: {name:?}");
+            io::Error::new(io::ErrorKind::InvalidData, msg)
+        })?;
+        let name = name.replace('\\', "/").trim_end_matches('/').to_string();
+        let mode = if entry.file_type().is_dir() || entry.path() == binary_path {
+            0o755
+        } else {
+            0o644
+        };
+        let options = options.unix_permissions(mode);
+        if entry.file_type().is_dir() {
+            zip.add_directory(format!("{name}/"), options)?;
+        } else {
+            zip.start_file(name, options)?;
+            io::copy(&mut File::open(entry.path())?, &mut zip)?;
+        }
+    }
+    zip.finish()?.flush()?;
+    Ok(())
+}
+
+/// Converts a time in seconds since the Unix epoch to a zip timestamp, which
+/// can't be earlier than 1980.
+fn zip_datetime(timestamp: u64) -> zip::DateTime {
+    let datetime = time::OffsetDateTime::from_unix_timestamp(timestamp as i64)
+        .unwrap_or(time::OffsetDateTime::UNIX_EPOCH);
+    zip::DateTime::from_date_and_time(
+        datetime.year() as u16,
+        datetime.month() as u8,
+        datetime.day(),
+        datetime.hour(),
+        datetime.minute(),
+        datetime.second(),
+    )
+    .unwrap_or_default()
+}
@@ -0,0 +1,504 @@
+// Checks the bundle settings for problems before any bundles are created, for
+// `cargo bundle lint`.  Each check reports zero or more `Diagnostic`s, which
+// are grouped by the part of the settings they concern.
+
+use crate::bundle::linux::{self, deb_bundle, rpm_bundle};
+use crate::bundle::{self, osx_bundle, version, Bundle};
+use crate::image;
+use crate::Error;
+
+use ::image::png::PNGDecoder;
+use ::image::{GenericImage, ImageDecoder};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+
+/// How serious a problem found by `lint` is.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The bundle can't be created, or will be rejected or misbehave when
+    /// installed.
+    Error,
+    /// The bundle can be created, but is probably not what was intended.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem with the bundle settings found by `lint`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The part of the settings the problem concerns: `general`, `icons`, or
+    /// the short name of a bundle format.
+    pub group: &'static str,
+    pub message: String,
+}
+
+/// Icon sizes (in pixels, at standard density) each bundle format should
+/// have, so that the app looks right everywhere the icon is shown.
+fn expected_icon_sizes(bundle: Bundle) -> &'static [u32] {
+    match bundle {
+        Bundle::MacosApplication => &[16, 32, 128, 256, 512],
+        Bundle::IosApplication => &[120, 152, 180],
+        Bundle::LinuxDeb | Bundle::LinuxRpm | Bundle::LinuxAppImage => &[16, 32, 48, 128, 256],
+        // MSI installers use an `.ico` file, which holds its own sizes.
+        Bundle::WindowsMsi => &[],
+    }
+}
+
+/// Checks the settings for each of the bundles they would create, and returns
+/// the problems found, errors first.
+pub fn lint(settings: &bundle::Settings) -> Result<Vec<Diagnostic>, Error> {
+    let mut lint = Lint::default();
+    lint.check_identifier(settings);
+    lint.check_description(settings);
+    for bundle in settings.bundles()? {
+        let settings = settings.for_bundle(bundle)?;
+        let icon_sizes = lint.check_icons(&settings);
+        lint.check_icon_sizes(&settings, bundle, &icon_sizes);
+        match bundle {
+            Bundle::LinuxDeb => lint.check_deb(&settings),
+            Bundle::LinuxRpm => lint.check_rpm(&settings),
+            Bundle::WindowsMsi => lint.check_msi(&settings),
+            Bundle::MacosApplication => {
+                lint.check_apple_versions(&settings, bundle);
+                lint.check_osx(&settings);
+            }
+            Bundle::IosApplication => lint.check_apple_versions(&settings, bundle),
+            _ => {}
+        }
+    }
+    let mut diagnostics = lint.diagnostics;
+    diagnostics.sort_by_key(|diagnostic| diagnostic.severity);
+    Ok(diagnostics)
+}
+
+#[derive(Default)]
+struct Lint {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Lint {
+    fn report(&mut self, severity: Severity, group: &'static str, message: String) {
+        // Checks that run for each bundle format may find the same problem
+        // more than once.
+        if !self.diagnostics.iter().any(|d| d.message == message) {
+            self.diagnostics.push(Diagnostic {
+                severity,
+                group,
+                message,
+            });
+        }
+    }
+
+    fn check_identifier(&mut self, settings: &bundle::Settings) {
+        let identifier = settings.bundle_identifier();
+        if identifier.is_empty() {
+            self.report(
+                Severity::Error,
+                "general",
+                format!(
+                    "no `identifier` is set; set it to a reverse-DNS name like \
+                     \"com.example.{}\"",
+                    settings.package_name()
+                ),
+            );
+        } else if !is_reverse_dns(&identifier) {
+            // Identifiers derived from a binary's name often contain `_`, which
+            // is easy to fix.
+            let normalized = identifier.replace('_', "-");
+            if is_reverse_dns(&normalized) {
+                self.report(
+                    Severity::Warning,
+                    "general",
+                    format!(
+                        "identifier \"{identifier}\" contains `_`, which isn't allowed in \
+                         macOS and iOS bundle identifiers; set `identifier = \"{normalized}\"`"
+                    ),
+                );
+                return;
+            }
+            self.report(
+                Severity::Error,
+                "general",
+                format!(
+                    "identifier \"{identifier}\" is not a reverse-DNS name like \
+                     \"com.example.app\" (two or more parts separated by `.`, \
+                     made of letters, digits and `-`)"
+                ),
+            );
+        }
+    }
+
+    fn check_description(&mut self, settings: &bundle::Settings) {
+        if settings.short_description().is_empty() {
+            self.report(
+                Severity::Error,
+                "general",
+                "no description is set; set `short_description`, or the package's \
+                 `description`"
+                    .to_string(),
+            );
+        }
+    }
+
+    /// Checks that the icon files can be read and are square, and returns the
+    /// sizes of the square ones.
+    /// Returns the (square) sizes of the icons, reporting icons that can't be
+    /// found or read rather than failing, so that the other checks still run.
+    fn check_icons(&mut self, settings: &bundle::Settings) -> BTreeSet<u32> {
+        let mut sizes = BTreeSet::new();
+        for icon_path in settings.icon_files() {
+            let icon_path = match icon_path {
+                Ok(icon_path) => icon_path,
+                Err(error) => {
+                    self.report(Severity::Error, "icons", error.to_string());
+                    continue;
+                }
+            };
+            let dimensions = match icon_dimensions(&icon_path) {
+                Ok(dimensions) => dimensions,
+                Err(error) => {
+                    self.report(
+                        Severity::Error,
+                        "icons",
+                        format!("icon {} can't be read: {error}", icon_path.display()),
+                    );
+                    continue;
+                }
+            };
+            for (width, height) in dimensions {
+                if width == height {
+                    sizes.insert(width);
+                } else {
+                    self.report(
+                        Severity::Warning,
+                        "icons",
+                        format!(
+                            "icon {} is not square ({width}x{height})",
+                            icon_path.display()
+                        ),
+                    );
+                }
+            }
+        }
+        sizes
+    }
+
+    fn check_icon_sizes(
+        &mut self,
+        settings: &bundle::Settings,
+        bundle: Bundle,
+        sizes: &BTreeSet<u32>,
+    ) {
+        let format = bundle.short_name();
+        if bundle == Bundle::WindowsMsi {
+            let mut icon_files = settings.icon_files();
+            let has_ico = icon_files.any(|path| {
+                path.map(|path| path.extension() == Some(OsStr::new("ico")))
+                    .unwrap_or(false)
+            });
+            if !has_ico {
+                self.report(
+                    Severity::Warning,
+                    "icons",
+                    format!("no `.ico` icon is given, so the {format} installer has no icon"),
+                );
+            }
+            return;
+        }
+        if sizes.is_empty() {
+            let severity = match bundle {
+                // The AppImage desktop integration requires an icon.
+                Bundle::LinuxAppImage => Severity::Error,
+                _ => Severity::Warning,
+            };
+            self.report(
+                severity,
+                "icons",
+                format!("no usable icons are given for the {format} bundle"),
+            );
+            return;
+        }
+        let missing: Vec<String> = expected_icon_sizes(bundle)
+            .iter()
+            .filter(|size| !sizes.contains(size))
+            .map(|size| format!("{size}x{size}"))
+            .collect();
+        if !missing.is_empty() {
+            self.report(
+                Severity::Warning,
+                "icons",
+                format!(
+                    "the {format} bundle should have icons of size {}",
+                    missing.join(", ")
+                ),
+            );
+        }
+    }
+
+    fn check_deb(&mut self, settings: &bundle::Settings) {
+        let name = linux::package_name(settings);
+        if !deb_bundle::is_debian_package_name(&name) {
+            self.report(
+                Severity::Error,
+                "deb",
+                format!(
+                    "package name \"{name}\" is not allowed by Debian policy (it must be \
+                     at least two characters of a-z, 0-9, `+`, `-` and `.`, starting \
+                     with a letter or digit); change `name`"
+                ),
+            );
+        }
+
+        let version = settings.deb_version_string();
+        if !version::is_debian_version(&version) {
+            self.report(
+                Severity::Error,
+                "deb",
+                format!(
+                    "version \"{version}\" is not a valid Debian version (it must be \
+                     [EPOCH:]UPSTREAM[-REVISION], where the upstream version starts with \
+                     a digit, and contains only letters, digits, `.`, `+`, `~` and `-`); \
+                     set `deb_version`"
+                ),
+            );
+        }
+
+        for problem in deb_bundle::control_field_problems(settings) {
+            self.report(Severity::Error, "deb", problem);
+        }
+
+        for script in ["preinst", "postinst", "prerm", "postrm"] {
+            if let Some(setting) = settings.deb_maintainer_script(script) {
+                if let Err(error) = deb_bundle::read_maintainer_script(setting) {
+                    self.report(Severity::Error, "deb", error.to_string());
+                }
+            }
+        }
+
+        if let Err(error) = settings.deb_compression().check_available() {
+            self.report(Severity::Error, "deb", error.to_string());
+        }
+        if let Err(error) = settings.deb_compression_level() {
+            self.report(Severity::Error, "deb", error.to_string());
+        }
+
+        let dpkg_dir = settings.deb_dpkg_dir();
+        if settings.deb_shlibdeps() && !dpkg_dir.join("info").is_dir() {
+            self.report(
+                Severity::Warning,
+                "deb",
+                format!(
+                    "`deb_shlibdeps` is set, but there is no dpkg database at {} to find \
+                     shared libraries in; set `deb_dpkg_dir`",
+                    dpkg_dir.display()
+                ),
+            );
+        }
+    }
+
+    fn check_rpm(&mut self, settings: &bundle::Settings) {
+        let version = settings.rpm_version_string();
+        if let Err(error) = rpm_bundle::check_version_parts(&version, settings.rpm_release()) {
+            self.report(Severity::Error, "rpm", error.to_string());
+        }
+    }
+
+    fn check_msi(&mut self, settings: &bundle::Settings) {
+        if let Err(error) = settings.msi_version_string() {
+            self.report(Severity::Error, "msi", error.to_string());
+        }
+    }
+
+    fn check_apple_versions(&mut self, settings: &bundle::Settings, bundle: Bundle) {
+        let (key, bundle_version) = match bundle {
+            Bundle::IosApplication => ("ios_bundle_version", settings.ios_bundle_version()),
+            _ => ("osx_bundle_version", settings.osx_bundle_version()),
+        };
+        let short_version = settings.apple_short_version_string();
+        let versions = [
+            ("CFBundleShortVersionString", short_version, "version"),
+            ("CFBundleVersion", bundle_version, key),
+        ];
+        for (plist_key, version, key) in versions {
+            if !version::is_apple_version(&version) {
+                self.report(
+                    Severity::Error,
+                    bundle.short_name(),
+                    format!(
+                        "{plist_key} \"{version}\" is not a valid bundle version, which \
+                         must be up to three numbers separated by `.`; set `{key}`"
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_osx(&mut self, settings: &bundle::Settings) {
+        for framework in settings.osx_frameworks() {
+            if let Err(error) = osx_bundle::locate_framework(framework) {
+                self.report(Severity::Error, "osx", error.to_string());
+            }
+        }
+    }
+}
+
+/// Returns the dimensions of the standard-density images in an icon file;
+/// `.ico` files and retina (`@2x`) images have none.
+fn icon_dimensions(icon_path: &Path) -> Result<Vec<(u32, u32)>, Error> {
+    let mut dimensions = Vec::new();
+    if icon_path.extension() == Some(OsStr::new("icns")) {
+        let icon_family = icns::IconFamily::read(File::open(icon_path)?)?;
+        for icon_type in icon_family.available_icons() {
+            if icon_type.pixel_density() == 1 {
+                dimensions.push((icon_type.screen_width(), icon_type.screen_height()));
+            }
+        }
+    } else if icon_path.extension() != Some(OsStr::new("ico")) && !image::is_retina(icon_path) {
+        if icon_path.extension() == Some(OsStr::new("png")) {
+            let mut decoder = PNGDecoder::new(File::open(icon_path)?);
+            dimensions.push(decoder.dimensions()?);
+        } else {
+            dimensions.push(::image::open(icon_path)?.dimensions());
+        }
+    }
+    Ok(dimensions)
+}
+
+/// Returns true if `identifier` looks like a reverse-DNS name, such as
+/// `com.example.app`.
+fn is_reverse_dns(identifier: &str) -> bool {
+    let parts: Vec<&str> = identifier.split('.').collect();
+    parts.len() >= 2
+        && parts.iter().all(|part| {
+            !part.is_empty()
+                && !part.starts_with('-')
+                && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_reverse_dns, lint, Severity};
+    use crate::{BuildArtifact, Bundle, SettingsBuilder};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn reverse_dns_identifiers() {
+        assert!(is_reverse_dns("com.example.app"));
+        assert!(is_reverse_dns("com.example.my-app2"));
+        assert!(!is_reverse_dns(""));
+        assert!(!is_reverse_dns("app"));
+        assert!(!is_reverse_dns("com..app"));
+        assert!(!is_reverse_dns("com.example.my_app"));
+    }
+
+    #[test]
+    fn default_identifier_with_underscore() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(
+            temp_dir.path().join("Cargo.toml"),
+            "[package]\n\
+             name = \"example\"\n\
+             version = \"0.1.0\"\n\
+             description = \"An example\"\n",
+        )
+        .unwrap();
+        fs::create_dir_all(temp_dir.path().join("src/bin")).unwrap();
+        fs::write(temp_dir.path().join("src/bin/my_tool.rs"), "fn main() {}\n").unwrap();
+
+        let settings = SettingsBuilder::new(temp_dir.path())
+            .artifact(BuildArtifact::Bin("my_tool".to_string()))
+            .build()
+            .unwrap();
+        let diagnostics = lint(&settings[0]).unwrap();
+        let diagnostic = diagnostics
+            .iter()
+            .find(|diagnostic| diagnostic.message.contains("my_tool.example"))
+            .unwrap();
+        assert_eq!(diagnostic.severity, Severity::Warning);
+        assert!(diagnostic
+            .message
+            .contains("set `identifier = \"my-tool.example\"`"));
+    }
+
+    #[test]
+    fn deb_without_maintainer() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(
+            temp_dir.path().join("Cargo.toml"),
+            "[package]\n\
+             name = \"example\"\n\
+             version = \"0.1.0\"\n\
+             description = \"An example\"\n",
+        )
+        .unwrap();
+        fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+
+        let settings = SettingsBuilder::new(temp_dir.path())
+            .format(Bundle::LinuxDeb)
+            .build()
+            .unwrap();
+        let diagnostics = lint(&settings[0]).unwrap();
+        let diagnostic = diagnostics
+            .iter()
+            .find(|diagnostic| diagnostic.message.contains("no maintainer"))
+            .unwrap();
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert!(diagnostic.message.contains("`deb_maintainer`"));
+        assert!(diagnostic.message.contains("`authors`"));
+    }
+
+    #[test]
+    fn unreadable_icon() {
+        let temp_dir = tempdir().unwrap();
+        let icon_path = temp_dir.path().join("icon.png");
+        fs::write(&icon_path, "not a PNG image").unwrap();
+        fs::write(
+            temp_dir.path().join("Cargo.toml"),
+            format!(
+                "[package]\n\
+                 name = \"example\"\n\
+                 version = \"0.1.0\"\n\
+                 \n\
+                 [package.metadata.bundle]\n\
+                 icon = [{:?}]\n",
+                icon_path.display().to_string()
+            ),
+        )
+        .unwrap();
+        fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+
+        let settings = SettingsBuilder::new(temp_dir.path())
+            .format(Bundle::LinuxDeb)
+            .build()
+            .unwrap();
+        let diagnostics = lint(&settings[0]).unwrap();
+        let diagnostic = diagnostics
+            .iter()
+            .find(|diagnostic| diagnostic.group == "icons")
+            .unwrap();
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert!(diagnostic.message.contains("icon.png"));
+        // The other checks still run.
+        assert!(diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.message.contains("no maintainer")));
+    }
+}
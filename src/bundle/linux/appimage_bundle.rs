@@ -0,0 +1,144 @@
+// An AppImage is a single executable file laid out like:
+//
+// foobar-1.2.3-x86_64.AppImage
+//     runtime                 # An ELF executable which mounts the image below
+//     squashfs image          # The AppDir, containing:
+//         AppRun                               # Entry point; runs usr/bin/foobar
+//         foobar.desktop                       # Desktop file (required)
+//         foobar.png                           # Icon named by the desktop file
+//         .DirIcon                             # Icon shown by file managers
+//         usr/bin/foobar                       # Binary executable file
+//         usr/share/applications/foobar.desktop
+//         usr/share/icons/hicolor/...          # Icon files
+//         usr/lib/foobar/...                   # Other resource files
+//
+// The contents of `usr/` are the same as the data files of a `deb` package.
+// The runtime is not built by cargo-bundle; it is copied from the local path
+// given by the `appimage_runtime` setting (prebuilt runtimes are published at
+// https://github.com/AppImage/type2-runtime/releases).  See
+// https://docs.appimage.org/reference/appdir.html for more information.
+
+use crate::bundle;
+use crate::bundle::linux::squashfs;
+use crate::bundle::linux::{
+    create_file_with_data, generate_desktop_file, generate_icon_files, set_executable,
+    transfer_resource_files,
+};
+use crate::file;
+use crate::terminal;
+use crate::Error;
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+pub fn bundle_project(settings: &bundle::Settings) -> Result<Vec<PathBuf>, Error> {
+    let runtime_path = match settings.appimage_runtime() {
+        Some(path) => PathBuf::from(path),
+        None => return Err(Error::AppImageRuntimeNotConfigured),
+    };
+    let runtime = fs::read(&runtime_path)?;
+    if !runtime.starts_with(b"\x7fELF") {
+        return Err(Error::AppImageRuntimeNotValid(runtime_path));
+    }
+
+    let arch = match settings.binary_arch() {
+        "x86" => "i686",
+        "arm" => "armhf",
+        other => other,
+    };
+    let base_name = format!(
+        "{}-{}-{}",
+        settings.bundle_name().replace(' ', "_"),
+        settings.version_string(),
+        arch
+    );
+    let appimage_name = format!("{base_name}.AppImage");
+    terminal::print_bundling(&appimage_name)?;
+
+    let base_dir = settings.project_out_directory().join("bundle/appimage");
+    let app_dir = base_dir.join(format!("{base_name}.AppDir"));
+    if app_dir.exists() {
+        fs::remove_dir_all(&app_dir)?;
+    }
+
+    let binary_dest = app_dir.join("usr/bin").join(settings.binary_name());
+    file::copy(settings.binary_path(), &binary_dest)?;
+    transfer_resource_files(settings, &app_dir)?;
+    generate_icon_files(settings, &app_dir)?;
+    generate_desktop_file(settings, &app_dir)?;
+    generate_app_dir_root(settings, &app_dir)?;
+
+    let image_path = base_dir.join(format!("{base_name}.squashfs"));
+    let mtime = u32::try_from(settings.timestamp())
+        .map_err(|_| Error::InvalidSourceDateEpoch(settings.timestamp().to_string()))?;
+    squashfs::create_image(&app_dir, &image_path, mtime)?;
+
+    let appimage_path = base_dir.join(&appimage_name);
+    let mut appimage = file::create(&appimage_path)?;
+    appimage.write_all(&runtime)?;
+    std::io::copy(&mut fs::File::open(&image_path)?, &mut appimage)?;
+    appimage.flush()?;
+    fs::remove_file(&image_path)?;
+    set_executable(&appimage_path)?;
+
+    Ok(vec![appimage_path])
+}
+
+/// Creates the files the AppImage runtime and desktop integration tools look
+/// for at the root of the AppDir: the `AppRun` entry point, a desktop file,
+/// and the application icon.
+fn generate_app_dir_root(settings: &bundle::Settings, app_dir: &Path) -> Result<(), Error> {
+    let bin_name = settings.binary_name();
+    create_file_with_data(
+        app_dir.join("AppRun"),
+        &format!(
+            "#!/bin/sh\n\
+             HERE=\"$(dirname \"$(readlink -f \"$0\")\")\"\n\
+             exec \"$HERE/usr/bin/{bin_name}\" \"$@\"\n"
+        ),
+    )?;
+    set_executable(&app_dir.join("AppRun"))?;
+
+    let desktop_file_name = format!("{bin_name}.desktop");
+    file::copy(
+        &app_dir
+            .join("usr/share/applications")
+            .join(&desktop_file_name),
+        &app_dir.join(desktop_file_name),
+    )?;
+
+    let icon_path = largest_icon(&app_dir.join("usr/share/icons/hicolor"))?
+        .ok_or(Error::UsableIconFilesNotFound)?;
+    file::copy(&icon_path, &app_dir.join(format!("{bin_name}.png")))?;
+    file::copy(&icon_path, &app_dir.join(".DirIcon"))?;
+    Ok(())
+}
+
+/// Returns the largest of the icons generated under the hicolor theme
+/// directory, whose subdirectories are named like `32x32` or `32x32@2x`.
+fn largest_icon(hicolor_dir: &Path) -> Result<Option<PathBuf>, Error> {
+    if !hicolor_dir.exists() {
+        return Ok(None);
+    }
+    let mut largest: Option<(u32, PathBuf)> = None;
+    for entry in WalkDir::new(hicolor_dir).sort_by(|a, b| a.file_name().cmp(b.file_name())) {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let rel_path = entry.path().strip_prefix(hicolor_dir).unwrap();
+        let size = rel_path
+            .components()
+            .next()
+            .and_then(|dir| dir.as_os_str().to_str())
+            .and_then(|dir| dir.split('x').next())
+            .and_then(|width| width.parse::<u32>().ok())
+            .unwrap_or(0);
+        if largest.as_ref().is_none_or(|(max, _)| size > *max) {
+            largest = Some((size, entry.into_path()));
+        }
+    }
+    Ok(largest.map(|(_, path)| path))
+}
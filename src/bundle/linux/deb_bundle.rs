@@ -5,32 +5,54 @@
 //     control.tar.gz          # Contains files controlling the installation:
 //         control                  # Basic package metadata
 //         md5sums                  # Checksums for files in data.tar.gz below
+//         preinst                  # Pre-installation script (optional)
 //         postinst                 # Post-installation script (optional)
 //         prerm                    # Pre-uninstallation script (optional)
+//         postrm                   # Post-uninstallation script (optional)
 //     data.tar.gz             # Contains files to be installed:
 //         usr/bin/foobar                            # Binary executable file
 //         usr/share/applications/foobar.desktop     # Desktop file (for apps)
 //         usr/share/icons/hicolor/...               # Icon files (for apps)
 //         usr/lib/foobar/...                        # Other resource files
 //
-// For cargo-bundle, we put bundle resource files under /usr/lib/package_name/,
+// For cargo-bundle, we put bundle resource files under /usr/lib/<package>/,
 // and then generate the desktop file and control file from the bundle
-// metadata, as well as generating the md5sums file.  Currently we do not
-// generate postinst or prerm files.
+// metadata, as well as generating the md5sums file.  The maintainer scripts
+// are the ones given in the bundle metadata, merged with snippets that update
+// the desktop file database and icon cache after the package's desktop file
+// and icons are installed or removed.  The control and data archives are
+// compressed with gzip by default, which every version of dpkg can unpack, or
+// with xz or zstd (e.g. `data.tar.xz`), or not at all (`data.tar`).
 
 use crate::bundle;
 use crate::bundle::linux::{
     create_file_with_data, generate_desktop_file, generate_icon_files, generate_md5sum,
-    tar_and_gzip_dir, total_dir_size,
+    package_name, set_executable, shlibdeps, tar_and_compress_dir, total_dir_size,
+    transfer_resource_files,
 };
+use crate::bundle::version::is_debian_version;
 use crate::file;
 use crate::terminal;
 use crate::Error;
 
+use std::fs::{self, File};
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
+/// The maintainer scripts a package can have, which are run by `dpkg` when the
+/// package is installed, upgraded or removed.  See
+/// https://www.debian.org/doc/debian-policy/ch-maintainerscripts.html
+const MAINTAINER_SCRIPTS: &[&str] = &["preinst", "postinst", "prerm", "postrm"];
+
+/// The `#!` line added to a maintainer script given without one.
+const SCRIPT_HEADER: &str = "#!/bin/sh\n";
+
+/// The start of a maintainer script made up only of generated snippets.  A
+/// script given in the settings keeps its own error handling, which (as with
+/// debhelper) the snippets merged into it follow.
+const GENERATED_SCRIPT_HEADER: &str = "#!/bin/sh\nset -e\n";
+
 pub fn bundle_project(settings: &bundle::Settings) -> Result<Vec<PathBuf>, Error> {
     let arch = match settings.binary_arch() {
         "x86" => "i386",
@@ -40,12 +62,12 @@ pub fn bundle_project(settings: &bundle::Settings) -> Result<Vec<PathBuf>, Error
         other => other,
     };
 
-    let package_base_name = format!(
-        "{}_{}_{}",
-        settings.binary_name(),
-        settings.version_string(),
-        arch
-    );
+    // As in Debian's own file names, the epoch is left out of the file name.
+    let version = settings.deb_version_string();
+    let version = version
+        .split_once(':')
+        .map_or(&*version, |(_, version)| version);
+    let package_base_name = format!("{}_{}_{}", settings.binary_name(), version, arch);
 
     let package_name = format!("{package_base_name}.deb");
     let base_dir = settings.project_out_directory().join("bundle/deb");
@@ -53,6 +75,12 @@ pub fn bundle_project(settings: &bundle::Settings) -> Result<Vec<PathBuf>, Error
 
     terminal::print_bundling(&package_name)?;
 
+    if let Some(problem) = control_field_problems(settings).into_iter().next() {
+        return Err(Error::InvalidControlField(problem));
+    }
+    settings.deb_compression().check_available()?;
+    let level = settings.deb_compression_level()?;
+
     if package_dir.exists() {
         std::fs::remove_dir_all(&package_dir)?;
     }
@@ -64,25 +92,31 @@ pub fn bundle_project(settings: &bundle::Settings) -> Result<Vec<PathBuf>, Error
     let binary_dest = data_dir.join("usr/bin").join(settings.binary_name());
     file::copy(settings.binary_path(), &binary_dest)?;
 
+    transfer_resource_files(settings, &data_dir)?;
     generate_icon_files(settings, &data_dir)?;
     generate_desktop_file(settings, &data_dir)?;
 
     // Generate control files.
     let control_dir = package_dir.join("control");
-    generate_control_file(settings, arch, &control_dir, &data_dir)?;
+    let depends = shlibdeps::depends(settings, arch)?;
+    generate_control_file(settings, arch, &depends, &control_dir, &data_dir)?;
     generate_md5sums(&control_dir, &data_dir)?;
+    generate_maintainer_scripts(settings, &control_dir, &data_dir)?;
 
     // Generate `debian-binary` file; see
     // http://www.tldp.org/HOWTO/Debian-Binary-Package-Building-HOWTO/x60.html#AEN66
     let debian_binary_path = package_dir.join("debian-binary");
     create_file_with_data(&debian_binary_path, "2.0\n")?;
 
-    // Apply tar/gzip/ar to create the final package file.
-    let control_tar_gz_path = tar_and_gzip_dir(control_dir)?;
-    let data_tar_gz_path = tar_and_gzip_dir(data_dir)?;
+    // Apply tar/compression/ar to create the final package file.
+    let mtime = settings.timestamp();
+    let compression = settings.deb_compression();
+    let control_tar_path = tar_and_compress_dir(control_dir, compression, level, mtime)?;
+    let data_tar_path = tar_and_compress_dir(data_dir, compression, level, mtime)?;
 
     create_archive(
-        vec![debian_binary_path, control_tar_gz_path, data_tar_gz_path],
+        vec![debian_binary_path, control_tar_path, data_tar_path],
+        mtime,
         &package_path,
     )?;
 
@@ -92,6 +126,7 @@ pub fn bundle_project(settings: &bundle::Settings) -> Result<Vec<PathBuf>, Error
 fn generate_control_file(
     settings: &bundle::Settings,
     arch: &str,
+    depends: &[String],
     control_dir: &Path,
     data_dir: &Path,
 ) -> Result<(), Error> {
@@ -99,28 +134,43 @@ fn generate_control_file(
     // https://www.debian.org/doc/debian-policy/ch-controlfields.html
     let dest_path = control_dir.join("control");
     let mut file = file::create(&dest_path)?;
-    writeln!(
-        &mut file,
-        "Package: {}",
-        str::replace(settings.bundle_name(), " ", "-").to_ascii_lowercase()
-    )?;
-    writeln!(&mut file, "Version: {}", settings.version_string())?;
+    writeln!(&mut file, "Package: {}", package_name(settings))?;
+    writeln!(&mut file, "Version: {}", settings.deb_version_string())?;
     writeln!(&mut file, "Architecture: {arch}")?;
     // deb Installed-Size is size in bytes / 1024
     // https://www.debian.org/doc/debian-policy/ch-controlfields.html#s-f-installed-size
     writeln!(
         &mut file,
         "Installed-Size: {}",
-        (total_dir_size(data_dir)? + 1023) / 1024
+        total_dir_size(data_dir)?.div_ceil(1024)
     )?;
-    let authors = settings.authors_comma_separated().unwrap_or_default();
-    writeln!(&mut file, "Maintainer: {authors}")?;
+    if let Some(maintainer) = settings.deb_maintainer() {
+        writeln!(&mut file, "Maintainer: {maintainer}")?;
+    }
+    if let Some(section) = settings.deb_section() {
+        writeln!(&mut file, "Section: {section}")?;
+    }
+    if let Some(priority) = settings.deb_priority() {
+        writeln!(&mut file, "Priority: {}", priority.as_str())?;
+    }
+    if settings.deb_essential() {
+        writeln!(&mut file, "Essential: yes")?;
+    }
     if !settings.homepage_url().is_empty() {
         writeln!(&mut file, "Homepage: {}", settings.homepage_url())?;
     }
-    let dependencies = settings.debian_dependencies();
-    if !dependencies.is_empty() {
-        writeln!(&mut file, "Depends: {}", dependencies.join(", "))?;
+    for (field, relations) in settings.debian_relations() {
+        let relations = if field == "Depends" {
+            depends
+        } else {
+            relations
+        };
+        if !relations.is_empty() {
+            writeln!(&mut file, "{field}: {}", relations.join(", "))?;
+        }
+    }
+    for (name, value) in settings.deb_fields() {
+        writeln!(&mut file, "{name}: {}", value.trim())?;
     }
     let mut short_description = settings.short_description().trim();
     if short_description.is_empty() {
@@ -143,13 +193,300 @@ fn generate_control_file(
     Ok(())
 }
 
+/// Returns the problems with the settings for the fields of the control file,
+/// which would make it invalid.
+pub(crate) fn control_field_problems(settings: &bundle::Settings) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (field, relations) in settings.debian_relations() {
+        let key = format!("deb_{}", field.to_ascii_lowercase().replace('-', "_"));
+        for relation in relations {
+            if !is_debian_relation(relation, field == "Provides") {
+                let example = match field {
+                    "Provides" => "\"libfoo (= 1.2)\"",
+                    _ => "\"libfoo (>= 1.2) | libbar\"",
+                };
+                problems.push(format!(
+                    "`{relation}` in `{key}` is not a valid {field} relation (e.g. {example})"
+                ));
+            }
+        }
+    }
+    for (soname, relations) in settings.deb_shlibdeps_overrides().into_iter().flatten() {
+        let invalid = relations
+            .split(',')
+            .map(str::trim)
+            .find(|relation| !is_debian_relation(relation, false));
+        if !relations.trim().is_empty() && invalid.is_some() {
+            problems.push(format!(
+                "`deb_shlibdeps_overrides` value for `{soname}` is not a valid Depends relation \
+                 (e.g. \"libfoo (>= 1.2)\", or \"\" for none)"
+            ));
+        }
+    }
+    match settings.deb_maintainer() {
+        Some(maintainer) if !is_maintainer(maintainer) => problems.push(format!(
+            "maintainer \"{maintainer}\" is not of the form \"Name <email>\"; set \
+             `deb_maintainer`"
+        )),
+        Some(_) => {}
+        None => problems.push(
+            "no maintainer is set, but the `Maintainer` field is required; set \
+             `deb_maintainer` to \"Name <email>\", or the package's `authors`"
+                .to_string(),
+        ),
+    }
+    if let Some(section) = settings.deb_section() {
+        if !is_section(section) {
+            problems.push(format!(
+                "section \"{section}\" is not a valid Debian section (e.g. \"utils\" or \
+                 \"contrib/games\")"
+            ));
+        }
+    }
+    for (name, value) in settings.deb_fields() {
+        let valid_name = name.len() > 2
+            && name.starts_with("X-")
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !valid_name {
+            problems.push(format!(
+                "`deb_fields` name `{name}` must start with `X-`, followed by letters, \
+                 digits and `-`"
+            ));
+        }
+        if value.trim().is_empty() || value.trim().contains('\n') {
+            problems.push(format!(
+                "`deb_fields` value for `{name}` must be a single, non-empty line"
+            ));
+        }
+    }
+    problems
+}
+
+/// Returns true if `name` is a valid Debian package name.  See
+/// https://www.debian.org/doc/debian-policy/ch-controlfields.html#source
+pub(crate) fn is_debian_package_name(name: &str) -> bool {
+    name.len() >= 2
+        && name.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "+-.".contains(c))
+}
+
+/// Returns true if `relation` is a valid entry in a relationship field, such
+/// as `libfoo:amd64 (>= 1.2) [linux-any] <!nocheck> | libbar`.  Entries in the
+/// `Provides` field can't have alternatives, and can only have `=` versions.
+/// See https://www.debian.org/doc/debian-policy/ch-relationships.html
+fn is_debian_relation(relation: &str, provides: bool) -> bool {
+    let alternatives: Vec<&str> = relation.split('|').collect();
+    if provides && alternatives.len() > 1 {
+        return false;
+    }
+    alternatives.into_iter().all(|alternative| {
+        let alternative = alternative.trim();
+        let name_end = alternative
+            .find(|c: char| c.is_whitespace() || "([<".contains(c))
+            .unwrap_or(alternative.len());
+        let (name, mut rest) = alternative.split_at(name_end);
+        let (name, arch) = match name.split_once(':') {
+            Some((name, arch)) => (name, Some(arch)),
+            None => (name, None),
+        };
+        if !is_debian_package_name(name) || arch.is_some_and(|arch| !is_arch_name(arch)) {
+            return false;
+        }
+        rest = rest.trim_start();
+        if let Some(version) = rest.strip_prefix('(') {
+            let Some((version, after)) = version.split_once(')') else {
+                return false;
+            };
+            let version = version.trim();
+            let Some(op) = ["<<", "<=", ">=", ">>", "="]
+                .into_iter()
+                .find(|op| version.starts_with(op))
+            else {
+                return false;
+            };
+            if (provides && op != "=") || !is_debian_version(version[op.len()..].trim()) {
+                return false;
+            }
+            rest = after.trim_start();
+        }
+        if let Some(archs) = rest.strip_prefix('[') {
+            let Some((archs, after)) = archs.split_once(']') else {
+                return false;
+            };
+            let mut archs = archs.split_whitespace().peekable();
+            if archs.peek().is_none()
+                || !archs.all(|arch| is_arch_name(arch.strip_prefix('!').unwrap_or(arch)))
+            {
+                return false;
+            }
+            rest = after.trim_start();
+        }
+        while let Some(profiles) = rest.strip_prefix('<') {
+            let Some((profiles, after)) = profiles.split_once('>') else {
+                return false;
+            };
+            let mut profiles = profiles.split_whitespace().peekable();
+            if profiles.peek().is_none()
+                || !profiles.all(|profile| {
+                    let profile = profile.strip_prefix('!').unwrap_or(profile);
+                    !profile.is_empty()
+                        && profile.chars().all(|c| {
+                            c.is_ascii_lowercase() || c.is_ascii_digit() || "-.".contains(c)
+                        })
+                })
+            {
+                return false;
+            }
+            rest = after.trim_start();
+        }
+        rest.is_empty()
+    })
+}
+
+/// Returns true if `arch` looks like a Debian architecture name (e.g.
+/// `amd64`, `linux-any` or `any`).
+fn is_arch_name(arch: &str) -> bool {
+    !arch.is_empty()
+        && arch
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// Returns true if `maintainer` is of the form `Name <email>`.
+fn is_maintainer(maintainer: &str) -> bool {
+    let Some((name, email)) = maintainer.trim().split_once('<') else {
+        return false;
+    };
+    let Some(email) = email.strip_suffix('>') else {
+        return false;
+    };
+    // Several maintainers (separated by commas) aren't allowed.
+    !name.trim().is_empty()
+        && !name.contains(',')
+        && email.contains('@')
+        && !email.contains(|c: char| c.is_whitespace() || "<>,".contains(c))
+}
+
+/// Returns true if `section` is a valid Debian section, optionally in an
+/// archive area (e.g. `contrib/games`).
+fn is_section(section: &str) -> bool {
+    let section = match section.split_once('/') {
+        Some((area, section)) if is_arch_name(area) => section,
+        Some(_) => return false,
+        None => section,
+    };
+    section.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && section
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "+-.".contains(c))
+}
+
+/// Writes each maintainer script into the `control_dir`: the script given in
+/// the settings (if any), with the snippets generated for it (if any) merged
+/// in as debhelper would (see `merge_maintainer_script`).
+fn generate_maintainer_scripts(
+    settings: &bundle::Settings,
+    control_dir: &Path,
+    data_dir: &Path,
+) -> Result<(), Error> {
+    for script in MAINTAINER_SCRIPTS {
+        let snippets = maintainer_script_snippets(script, data_dir);
+        let contents = match settings.deb_maintainer_script(script) {
+            Some(setting) => {
+                merge_maintainer_script(script, &read_maintainer_script(setting)?, &snippets)
+            }
+            None if snippets.is_empty() => continue,
+            None => format!("{GENERATED_SCRIPT_HEADER}{snippets}"),
+        };
+        let script_path = control_dir.join(script);
+        create_file_with_data(&script_path, &contents)?;
+        set_executable(&script_path)?;
+    }
+    Ok(())
+}
+
+/// Returns the contents of a maintainer script given in the settings: the
+/// file it names if it is of the form `file:<path>`, or else the setting
+/// itself.
+pub(crate) fn read_maintainer_script(setting: &str) -> Result<String, Error> {
+    let script = match setting.strip_prefix("file:") {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|error| Error::MaintainerScript(PathBuf::from(path), error))?,
+        None => setting.to_string(),
+    };
+    Ok(script.replace("\r\n", "\n"))
+}
+
+/// Returns the shell snippets to run in the maintainer script named `script`
+/// for the files in the `data_dir`: updating the desktop file database and
+/// the icon cache after the package's desktop file and icons are installed
+/// (in `postinst`) or removed or purged (in `postrm`).
+fn maintainer_script_snippets(script: &str, data_dir: &Path) -> String {
+    let condition = match script {
+        "postinst" => "[ \"$1\" = \"configure\" ]",
+        "postrm" => "{ [ \"$1\" = \"remove\" ] || [ \"$1\" = \"purge\" ]; }",
+        _ => return String::new(),
+    };
+    let mut snippets = String::new();
+    if data_dir.join("usr/share/applications").is_dir() {
+        snippets.push_str(&format!(
+            "if {condition} && command -v update-desktop-database >/dev/null 2>&1; then\n\
+             \tupdate-desktop-database -q /usr/share/applications\n\
+             fi\n"
+        ));
+    }
+    if data_dir.join("usr/share/icons/hicolor").is_dir() {
+        snippets.push_str(&format!(
+            "if {condition} && command -v gtk-update-icon-cache >/dev/null 2>&1; then\n\
+             \tgtk-update-icon-cache -q -t -f /usr/share/icons/hicolor\n\
+             fi\n"
+        ));
+    }
+    snippets
+}
+
+/// Merges the generated `snippets` into the maintainer script named `name`
+/// (whose contents are `script`): they replace its `#DEBHELPER#` line if it
+/// has one.  Otherwise, as debhelper does, they are added to the end of a
+/// `preinst` or `postinst`, and to the start (right after the `#!` line) of a
+/// `prerm` or `postrm`.  A script without a `#!` line is run by `/bin/sh`.
+fn merge_maintainer_script(name: &str, script: &str, snippets: &str) -> String {
+    let (mut merged, body) = match script.split_once('\n') {
+        Some((first_line, body)) if first_line.starts_with("#!") => {
+            (format!("{first_line}\n"), body)
+        }
+        None if script.starts_with("#!") => (format!("{script}\n"), ""),
+        _ => (SCRIPT_HEADER.to_string(), script),
+    };
+    let is_token = |line: &str| line.trim() == "#DEBHELPER#";
+    let has_token = body.lines().any(is_token);
+    let prepend = matches!(name, "prerm" | "postrm");
+    if !has_token && prepend {
+        merged.push_str(snippets);
+    }
+    for line in body.lines() {
+        if is_token(line) {
+            merged.push_str(snippets);
+        } else {
+            merged.push_str(line);
+            merged.push('\n');
+        }
+    }
+    if !has_token && !prepend {
+        merged.push_str(snippets);
+    }
+    merged
+}
+
 /// Create an `md5sums` file in the `control_dir` containing the MD5 checksums
 /// for each file within the `data_dir`.
 fn generate_md5sums(control_dir: &Path, data_dir: &Path) -> Result<(), Error> {
     let md5sums_path = control_dir.join("md5sums");
     let mut md5sums_file = file::create(&md5sums_path)?;
 
-    for entry in WalkDir::new(data_dir) {
+    for entry in WalkDir::new(data_dir).sort_by(|a, b| a.file_name().cmp(b.file_name())) {
         let entry = entry?;
         let path = entry.path();
         if path.is_dir() {
@@ -169,14 +506,143 @@ fn generate_md5sums(control_dir: &Path, data_dir: &Path) -> Result<(), Error> {
 }
 
 /// Creates an `ar` archive from the given source files and writes it to the
-/// given destination path.
-fn create_archive(srcs: Vec<PathBuf>, dest: &Path) -> Result<(), Error> {
+/// given destination path.  The members are owned by root and stamped with
+/// `mtime`.
+fn create_archive(srcs: Vec<PathBuf>, mtime: u64, dest: &Path) -> Result<(), Error> {
     let mut builder = ar::Builder::new(file::create(dest)?);
 
     for path in &srcs {
-        builder.append_path(path)?;
+        let name = path.file_name().unwrap().to_string_lossy();
+        let mut header = ar::Header::new(name.as_bytes().to_vec(), path.metadata()?.len());
+        header.set_mtime(mtime);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_mode(0o100644);
+        builder.append(&header, File::open(path)?)?;
     }
 
     builder.into_inner()?.flush()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        is_debian_package_name, is_debian_relation, is_maintainer, is_section,
+        maintainer_script_snippets, merge_maintainer_script, read_maintainer_script,
+    };
+    use tempfile::tempdir;
+
+    #[test]
+    fn debian_package_names() {
+        assert!(is_debian_package_name("my-app"));
+        assert!(is_debian_package_name("libfoo2.0+bar"));
+        assert!(!is_debian_package_name("a"));
+        assert!(!is_debian_package_name("My-App"));
+        assert!(!is_debian_package_name("-app"));
+        assert!(!is_debian_package_name("my_app"));
+    }
+
+    #[test]
+    fn debian_relations() {
+        assert!(is_debian_relation("libc6", false));
+        assert!(is_debian_relation("libc6 (>= 2.31)", false));
+        assert!(is_debian_relation("libssl3 | libssl1.1 (>= 1.1.1)", false));
+        assert!(is_debian_relation("python3:any (>= 3.9~)", false));
+        assert!(is_debian_relation(
+            "foo [linux-any !hurd-i386] <!nocheck> <stage1>",
+            false
+        ));
+        assert!(is_debian_relation("virtual-app (= 1.0)", true));
+        assert!(!is_debian_relation("", false));
+        assert!(!is_debian_relation("libc6 >= 2.31", false));
+        assert!(!is_debian_relation("libc6 (~ 2.31)", false));
+        assert!(!is_debian_relation("libc6 (>= 2.31", false));
+        assert!(!is_debian_relation("libc6, libssl3", false));
+        assert!(!is_debian_relation("Foo", false));
+        assert!(!is_debian_relation("foo []", false));
+        assert!(!is_debian_relation("a-app | b-app", true));
+        assert!(!is_debian_relation("virtual-app (>= 1.0)", true));
+    }
+
+    #[test]
+    fn control_field_values() {
+        assert!(is_maintainer("Jane Doe <jane@example.com>"));
+        assert!(!is_maintainer("Jane Doe"));
+        assert!(!is_maintainer("<jane@example.com>"));
+        assert!(!is_maintainer(
+            "Jane Doe <jane@example.com>, John Doe <john@example.com>"
+        ));
+
+        assert!(is_section("utils"));
+        assert!(is_section("contrib/games"));
+        assert!(!is_section("Utils"));
+        assert!(!is_section("a/b/c"));
+    }
+
+    #[test]
+    fn merge_maintainer_scripts() {
+        let snippets = "update-caches\n";
+        assert_eq!(
+            merge_maintainer_script("postrm", "#!/bin/bash\nset -e\ncleanup\n", snippets),
+            "#!/bin/bash\nupdate-caches\nset -e\ncleanup\n"
+        );
+        assert_eq!(
+            merge_maintainer_script("postinst", "#!/bin/bash\nset -e\nsetup\n", snippets),
+            "#!/bin/bash\nset -e\nsetup\nupdate-caches\n"
+        );
+        assert_eq!(
+            merge_maintainer_script(
+                "postinst",
+                "#!/bin/sh\nsetup\n#DEBHELPER#\nexit 0\n",
+                snippets
+            ),
+            "#!/bin/sh\nsetup\nupdate-caches\nexit 0\n"
+        );
+        assert_eq!(
+            merge_maintainer_script("postrm", "cleanup\n", snippets),
+            "#!/bin/sh\nupdate-caches\ncleanup\n"
+        );
+        assert_eq!(
+            merge_maintainer_script("postinst", "setup", snippets),
+            "#!/bin/sh\nsetup\nupdate-caches\n"
+        );
+        assert_eq!(
+            merge_maintainer_script("postinst", "#!/bin/sh\nsetup\n#DEBHELPER#\n", ""),
+            "#!/bin/sh\nsetup\n"
+        );
+    }
+
+    #[test]
+    fn maintainer_scripts_from_settings() {
+        assert_eq!(
+            read_maintainer_script("#!/bin/sh\r\nsetup\r\n").unwrap(),
+            "#!/bin/sh\nsetup\n"
+        );
+        assert_eq!(read_maintainer_script("ldconfig").unwrap(), "ldconfig");
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("postinst");
+        std::fs::write(&path, "#!/bin/sh\nsetup\n").unwrap();
+        assert_eq!(
+            read_maintainer_script(&format!("file:{}", path.display())).unwrap(),
+            "#!/bin/sh\nsetup\n"
+        );
+        assert!(read_maintainer_script("file:missing/postinst").is_err());
+    }
+
+    #[test]
+    fn snippets_for_installed_files() {
+        let data_dir = tempdir().unwrap();
+        assert_eq!(maintainer_script_snippets("postinst", data_dir.path()), "");
+        std::fs::create_dir_all(data_dir.path().join("usr/share/applications")).unwrap();
+        std::fs::create_dir_all(data_dir.path().join("usr/share/icons/hicolor")).unwrap();
+        let postinst = maintainer_script_snippets("postinst", data_dir.path());
+        assert!(postinst.contains("update-desktop-database"));
+        assert!(postinst.contains("gtk-update-icon-cache"));
+        assert!(postinst.contains("\"configure\""));
+        let postrm = maintainer_script_snippets("postrm", data_dir.path());
+        assert!(postrm.contains("\"remove\""));
+        assert!(postrm.contains("\"purge\""));
+        assert_eq!(maintainer_script_snippets("preinst", data_dir.path()), "");
+    }
+}
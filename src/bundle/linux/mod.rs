@@ -1,6 +1,10 @@
+pub(crate) mod appimage_bundle;
 pub(crate) mod deb_bundle;
+pub(crate) mod rpm_bundle;
+mod shlibdeps;
+mod squashfs;
 
-use crate::bundle;
+use crate::bundle::{self, DebCompression};
 use crate::file;
 use crate::image;
 use crate::Error;
@@ -13,11 +17,23 @@ use walkdir::WalkDir;
 
 use std::collections::BTreeSet;
 use std::ffi::OsStr;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
+/// Returns the name of the package, as used in the `Package:` field of `deb`
+/// control files and the `Name` tag of `rpm` headers.
+pub fn package_name(settings: &bundle::Settings) -> String {
+    str::replace(settings.bundle_name(), " ", "-").to_ascii_lowercase()
+}
+
+/// Returns the directory under the `data_dir` that the package's resource
+/// files are installed in, and which the package owns.
+pub fn resource_dir(settings: &bundle::Settings, data_dir: &Path) -> PathBuf {
+    data_dir.join("usr/lib").join(package_name(settings))
+}
+
 /// Generate the application desktop file and store it under the `data_dir`.
 pub fn generate_desktop_file(settings: &bundle::Settings, data_dir: &Path) -> Result<(), Error> {
     let bin_name = settings.binary_name();
@@ -59,42 +75,153 @@ pub fn generate_desktop_file(settings: &bundle::Settings, data_dir: &Path) -> Re
     Ok(())
 }
 
-/// Creates a `.tar.gz` file from the given directory (placing the new file
-/// within the given directory's parent directory), then deletes the original
-/// directory and returns the path to the new file.
-pub fn tar_and_gzip_dir<P: AsRef<Path>>(src_dir: P) -> Result<PathBuf, Error> {
+/// Copy the bundle's resource files into the package's resource directory
+/// under the `data_dir`: `usr/lib/<package name>` (see `package_name`).
+pub fn transfer_resource_files(settings: &bundle::Settings, data_dir: &Path) -> Result<(), Error> {
+    let resource_dir = resource_dir(settings, data_dir);
+    for src in settings.resource_files() {
+        let src = src?;
+        let dest = resource_dir.join(file::resource_relpath(&src));
+        file::copy(&src, &dest)?;
+    }
+    Ok(())
+}
+
+/// Creates a `.tar` file from the given directory, compressed with
+/// `compression` at `level` (placing the new file within the given
+/// directory's parent directory, e.g. `data.tar.xz` for `data`), and returns
+/// the path to the new file.  See `create_tar_from_dir` for the `mtime`
+/// argument.
+#[cfg_attr(not(any(feature = "xz", feature = "zstd")), allow(unused_variables))]
+pub fn tar_and_compress_dir<P: AsRef<Path>>(
+    src_dir: P,
+    compression: DebCompression,
+    level: Option<u32>,
+    mtime: u64,
+) -> Result<PathBuf, Error> {
     let src_dir = src_dir.as_ref();
-    let dest_path = src_dir.with_extension("tar.gz");
+    let dest_path = match compression.extension() {
+        Some(extension) => src_dir.with_extension(format!("tar.{extension}")),
+        None => src_dir.with_extension("tar"),
+    };
+    let level = level
+        .or(compression.levels().map(|(_, default, _)| default))
+        .unwrap_or_default();
     let dest_file = file::create(&dest_path)?;
-    let gzip_encoder = gzip::Encoder::new(dest_file)?;
-    let gzip_encoder = create_tar_from_dir(src_dir, gzip_encoder)?;
-    let mut dest_file = gzip_encoder.finish().into_result()?;
+    let mut dest_file = match compression {
+        DebCompression::Gzip => {
+            let encoder = gzip_encoder(dest_file, mtime)?;
+            let encoder = create_tar_from_dir(src_dir, encoder, mtime)?;
+            encoder.finish().into_result()?
+        }
+        #[cfg(feature = "xz")]
+        DebCompression::Xz => {
+            let encoder = xz2::write::XzEncoder::new(dest_file, level);
+            let encoder = create_tar_from_dir(src_dir, encoder, mtime)?;
+            encoder.finish()?
+        }
+        #[cfg(feature = "zstd")]
+        DebCompression::Zstd => {
+            let encoder = zstd::stream::write::Encoder::new(dest_file, level as i32)?;
+            let encoder = create_tar_from_dir(src_dir, encoder, mtime)?;
+            encoder.finish()?
+        }
+        #[cfg(not(all(feature = "xz", feature = "zstd")))]
+        #[allow(unreachable_patterns)]
+        DebCompression::Xz | DebCompression::Zstd => {
+            return Err(Error::CompressionFeatureMissing(compression.as_str()));
+        }
+        DebCompression::None => create_tar_from_dir(src_dir, dest_file, mtime)?,
+    };
     dest_file.flush()?;
     Ok(dest_path)
 }
 
-/// Writes a tar file to the given writer containing the given directory.
-pub fn create_tar_from_dir<P: AsRef<Path>, W: Write>(src_dir: P, dest_file: W) -> Result<W, Error> {
+/// Returns a gzip encoder whose header records `mtime` (rather than the
+/// current time) as the modification time.
+pub fn gzip_encoder<W: Write>(dest_file: W, mtime: u64) -> Result<gzip::Encoder<W>, Error> {
+    let header = gzip::HeaderBuilder::new()
+        .modification_time(mtime as u32)
+        .finish();
+    let options = gzip::EncodeOptions::new().header(header);
+    Ok(gzip::Encoder::with_options(dest_file, options)?)
+}
+
+/// Writes a tar file to the given writer containing the given directory.  So
+/// that the archive only depends on the contents of the files, entries are
+/// written in order of their paths, owned by root and stamped with `mtime`,
+/// and files have mode 0755 or 0644 (see `file_mode`).
+pub fn create_tar_from_dir<P, W>(src_dir: P, dest_file: W, mtime: u64) -> Result<W, Error>
+where
+    P: AsRef<Path>,
+    W: Write,
+{
     let src_dir = src_dir.as_ref();
     let mut tar_builder = tar::Builder::new(dest_file);
-    for entry in WalkDir::new(src_dir) {
+    let entries = WalkDir::new(src_dir).sort_by(|a, b| a.file_name().cmp(b.file_name()));
+    for entry in entries {
         let entry = entry?;
         let src_path = entry.path();
         if src_path == src_dir {
             continue;
         }
         let dest_path = src_path.strip_prefix(src_dir).unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_username("root")?;
+        header.set_groupname("root")?;
+        header.set_mtime(mtime);
         if entry.file_type().is_dir() {
-            tar_builder.append_dir(dest_path, src_path)?;
+            header.set_entry_type(tar::EntryType::Directory);
+            header.set_mode(0o755);
+            header.set_size(0);
+            tar_builder.append_data(&mut header, dest_path, io::empty())?;
         } else {
+            header.set_entry_type(tar::EntryType::Regular);
+            let metadata = entry.metadata()?;
+            header.set_mode(file_mode(&metadata));
+            header.set_size(metadata.len());
             let mut src_file = File::open(src_path)?;
-            tar_builder.append_file(dest_path, &mut src_file)?;
+            tar_builder.append_data(&mut header, dest_path, &mut src_file)?;
         }
     }
     let dest_file = tar_builder.into_inner()?;
     Ok(dest_file)
 }
 
+/// Returns the mode to package a file with: 0755 if any of its execute
+/// permission bits are set, or 0644 otherwise, so that the mode doesn't depend
+/// on the umask it was created with.
+#[cfg(unix)]
+pub fn file_mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    if metadata.permissions().mode() & 0o111 != 0 {
+        0o755
+    } else {
+        0o644
+    }
+}
+
+#[cfg(not(unix))]
+pub fn file_mode(_metadata: &fs::Metadata) -> u32 {
+    0o644
+}
+
+/// Sets the execute permission bits of the file at `path` (where the platform
+/// has them), so that it is packaged with mode 0755.
+#[cfg(unix)]
+pub fn set_executable(path: &Path) -> Result<(), Error> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn set_executable(_path: &Path) -> Result<(), Error> {
+    Ok(())
+}
+
 /// Create an empty file at the given path, creating any parent directories as
 /// needed, then write `data` into the file.
 pub fn create_file_with_data<P: AsRef<Path>>(path: P, data: &str) -> Result<(), Error> {
@@ -237,7 +364,7 @@ mod tests {
     use tempfile::tempdir;
 
     #[test]
-    fn test_tar_and_gzip_dir() {
+    fn test_tar_and_compress_dir() {
         let temp_dir = tempdir().unwrap();
         std::fs::create_dir(temp_dir.path().join("foo")).unwrap();
         File::create(temp_dir.path().join("foo/file1.txt")).unwrap();
@@ -246,12 +373,129 @@ mod tests {
             .unwrap()
             .write_all(b"test")
             .unwrap();
-        let tar_gz_file = tar_and_gzip_dir(temp_dir.path().join("foo"));
-        assert!(tar_gz_file.is_ok());
-        let tar_gz_file = tar_gz_file.unwrap();
+        let compressions = [
+            (DebCompression::Gzip, None, "foo.tar.gz"),
+            (DebCompression::Xz, Some(9), "foo.tar.xz"),
+            (DebCompression::Zstd, None, "foo.tar.zst"),
+            (DebCompression::None, None, "foo.tar"),
+        ];
+        for (compression, level, file_name) in compressions {
+            if compression.check_available().is_err() {
+                assert!(
+                    tar_and_compress_dir(temp_dir.path().join("foo"), compression, level, 0)
+                        .is_err()
+                );
+                continue;
+            }
+            let tar_file = tar_and_compress_dir(temp_dir.path().join("foo"), compression, level, 0);
+            assert!(tar_file.is_ok());
+            let tar_file = tar_file.unwrap();
+
+            assert_eq!(tar_file, temp_dir.path().join(file_name));
+            assert!(tar_file.metadata().unwrap().len() > 0);
+            let file = File::open(&tar_file).unwrap();
+            let reader: Box<dyn io::Read> = match compression {
+                DebCompression::Gzip => Box::new(flate2::read::GzDecoder::new(file)),
+                #[cfg(feature = "xz")]
+                DebCompression::Xz => Box::new(xz2::read::XzDecoder::new(file)),
+                #[cfg(feature = "zstd")]
+                DebCompression::Zstd => Box::new(zstd::stream::read::Decoder::new(file).unwrap()),
+                DebCompression::None => Box::new(file),
+                #[allow(unreachable_patterns)]
+                _ => unreachable!(),
+            };
+            let paths: Vec<PathBuf> = tar::Archive::new(reader)
+                .entries()
+                .unwrap()
+                .map(|entry| entry.unwrap().path().unwrap().into_owned())
+                .collect();
+            assert_eq!(
+                paths,
+                ["file1.txt", "subdir", "subdir/file2.txt"].map(PathBuf::from)
+            );
+        }
+    }
+
+    #[test]
+    fn test_create_tar_from_dir_is_reproducible() {
+        let temp_dir = tempdir().unwrap();
+        let mut tars = Vec::new();
+        // The files are created in a different order in each directory.
+        for (dir, names) in [("foo", ["a.txt", "b.txt"]), ("bar", ["b.txt", "a.txt"])] {
+            let dir = temp_dir.path().join(dir);
+            std::fs::create_dir_all(dir.join("usr/bin")).unwrap();
+            File::create(dir.join("usr/bin/app")).unwrap();
+            set_executable(&dir.join("usr/bin/app")).unwrap();
+            for name in names {
+                File::create(dir.join(name))
+                    .unwrap()
+                    .write_all(name.as_bytes())
+                    .unwrap();
+            }
+            let tar = create_tar_from_dir(&dir, Vec::new(), 1234);
+            tars.push(tar.unwrap());
+        }
+        assert_eq!(tars[0], tars[1]);
+
+        let mut archive = tar::Archive::new(tars[0].as_slice());
+        let entries: Vec<(String, u32, u64, u64)> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let header = entry.unwrap().header().clone();
+                (
+                    header.path().unwrap().to_string_lossy().into_owned(),
+                    header.mode().unwrap(),
+                    header.uid().unwrap(),
+                    header.mtime().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                ("a.txt".to_string(), 0o644, 0, 1234),
+                ("b.txt".to_string(), 0o644, 0, 1234),
+                ("usr".to_string(), 0o755, 0, 1234),
+                ("usr/bin".to_string(), 0o755, 0, 1234),
+                ("usr/bin/app".to_string(), 0o755, 0, 1234),
+            ]
+        );
+    }
 
-        assert!(tar_gz_file.exists());
-        assert!(tar_gz_file.metadata().unwrap().len() > 0);
+    #[cfg(unix)]
+    #[test]
+    fn test_create_tar_from_dir_keeps_executable_bits() {
+        use std::os::unix::fs::PermissionsExt;
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("data");
+        let resource_dir = dir.join("usr/lib/app");
+        std::fs::create_dir_all(&resource_dir).unwrap();
+        for (name, mode) in [("helper.sh", 0o700), ("data.txt", 0o664)] {
+            let path = resource_dir.join(name);
+            File::create(&path).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        }
+        let tar = create_tar_from_dir(&dir, Vec::new(), 0).unwrap();
+
+        let mut archive = tar::Archive::new(tar.as_slice());
+        let modes: Vec<(String, u32)> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let header = entry.unwrap().header().clone();
+                let path = header.path().unwrap().to_string_lossy().into_owned();
+                (path, header.mode().unwrap())
+            })
+            .filter(|(path, _)| path.starts_with("usr/lib/app/"))
+            .collect();
+        assert_eq!(
+            modes,
+            vec![
+                ("usr/lib/app/data.txt".to_string(), 0o644),
+                ("usr/lib/app/helper.sh".to_string(), 0o755),
+            ]
+        );
     }
 
     #[test]
@@ -1,6 +1,746 @@
-use crate::Settings;
-use std::path::PathBuf;
+// The structure of an RPM package looks something like this:
+//
+// foobar-1.2.3-1.x86_64.rpm
+//     lead                # Legacy 96-byte header identifying the file as an RPM
+//     signature           # Header holding sizes and digests of what follows
+//     header              # Header holding the package metadata and file list
+//     payload             # Gzipped cpio ("newc") archive of the files:
+//         ./usr/bin/foobar                            # Binary executable file
+//         ./usr/share/applications/foobar.desktop     # Desktop file (for apps)
+//         ./usr/share/icons/hicolor/...               # Icon files (for apps)
+//         ./usr/lib/foobar/...                        # Other resource files
+//
+// See https://rpm-software-management.github.io/rpm/manual/format.html for a
+// description of the file format.  The payload is staged on disk using the
+// same layout as the data directory of a `deb` package, then written out
+// directly, so no `rpmbuild` installation is required.  Currently we do not
+// generate scriptlets or any dependencies beyond the `rpmlib()` features the
+// payload relies on.
 
-pub fn bundle_project(_settings: &Settings) -> crate::Result<Vec<PathBuf>> {
-    unimplemented!();
+use crate::bundle;
+use crate::bundle::linux::{
+    file_mode, generate_desktop_file, generate_icon_files, generate_md5sum, gzip_encoder,
+    resource_dir, transfer_resource_files,
+};
+use crate::bundle::version::is_rpm_version_part;
+use crate::file;
+use crate::terminal;
+use crate::Error;
+
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+const LEAD_MAGIC: [u8; 4] = [0xed, 0xab, 0xee, 0xdb];
+const HEADER_MAGIC: [u8; 8] = [0x8e, 0xad, 0xe8, 0x01, 0x00, 0x00, 0x00, 0x00];
+const LEAD_NAME_LEN: usize = 66;
+const LEAD_OS_LINUX: u16 = 1;
+const LEAD_SIGNATURE_TYPE: u16 = 5;
+
+// Header entry data types.
+const TYPE_INT16: u32 = 3;
+const TYPE_INT32: u32 = 4;
+const TYPE_STRING: u32 = 6;
+const TYPE_BIN: u32 = 7;
+const TYPE_STRING_ARRAY: u32 = 8;
+const TYPE_I18NSTRING: u32 = 9;
+
+// Signature header tags.
+const SIGTAG_HEADERSIGNATURES: u32 = 62;
+const SIGTAG_SHA256: u32 = 273;
+const SIGTAG_SIZE: u32 = 1000;
+const SIGTAG_MD5: u32 = 1004;
+const SIGTAG_PAYLOADSIZE: u32 = 1007;
+
+// Main header tags.
+const TAG_HEADERIMMUTABLE: u32 = 63;
+const TAG_HEADERI18NTABLE: u32 = 100;
+const TAG_NAME: u32 = 1000;
+const TAG_VERSION: u32 = 1001;
+const TAG_RELEASE: u32 = 1002;
+const TAG_SUMMARY: u32 = 1004;
+const TAG_DESCRIPTION: u32 = 1005;
+const TAG_BUILDTIME: u32 = 1006;
+const TAG_SIZE: u32 = 1009;
+const TAG_VENDOR: u32 = 1011;
+const TAG_LICENSE: u32 = 1014;
+const TAG_GROUP: u32 = 1016;
+const TAG_URL: u32 = 1020;
+const TAG_OS: u32 = 1021;
+const TAG_ARCH: u32 = 1022;
+const TAG_FILESIZES: u32 = 1028;
+const TAG_FILEMODES: u32 = 1030;
+const TAG_FILERDEVS: u32 = 1033;
+const TAG_FILEMTIMES: u32 = 1034;
+const TAG_FILEDIGESTS: u32 = 1035;
+const TAG_FILELINKTOS: u32 = 1036;
+const TAG_FILEFLAGS: u32 = 1037;
+const TAG_FILEUSERNAME: u32 = 1039;
+const TAG_FILEGROUPNAME: u32 = 1040;
+const TAG_SOURCERPM: u32 = 1044;
+const TAG_FILEVERIFYFLAGS: u32 = 1045;
+const TAG_PROVIDENAME: u32 = 1047;
+const TAG_REQUIREFLAGS: u32 = 1048;
+const TAG_REQUIRENAME: u32 = 1049;
+const TAG_REQUIREVERSION: u32 = 1050;
+const TAG_FILEDEVICES: u32 = 1095;
+const TAG_FILEINODES: u32 = 1096;
+const TAG_FILELANGS: u32 = 1097;
+const TAG_PROVIDEFLAGS: u32 = 1112;
+const TAG_PROVIDEVERSION: u32 = 1113;
+const TAG_DIRINDEXES: u32 = 1116;
+const TAG_BASENAMES: u32 = 1117;
+const TAG_DIRNAMES: u32 = 1118;
+const TAG_PAYLOADFORMAT: u32 = 1124;
+const TAG_PAYLOADCOMPRESSOR: u32 = 1125;
+const TAG_PAYLOADFLAGS: u32 = 1126;
+const TAG_PAYLOADDIGEST: u32 = 5092;
+const TAG_PAYLOADDIGESTALGO: u32 = 5093;
+
+// Dependency flags.
+const SENSE_LESS: u32 = 1 << 1;
+const SENSE_EQUAL: u32 = 1 << 3;
+const SENSE_RPMLIB: u32 = 1 << 24;
+
+// The `PGPHASHALGO_SHA256` value used for `PAYLOADDIGESTALGO`.
+const HASH_ALGO_SHA256: u32 = 8;
+
+pub fn bundle_project(settings: &bundle::Settings) -> Result<Vec<PathBuf>, Error> {
+    let arch = match settings.binary_arch() {
+        "x86" => "i686",
+        "arm" => "armv7hl",
+        other => other,
+    };
+    let name = super::package_name(settings);
+    let version = settings.rpm_version_string();
+    let release = settings.rpm_release();
+    check_version_parts(&version, release)?;
+
+    let package_base_name = format!("{name}-{version}-{release}.{arch}");
+    let package_name = format!("{package_base_name}.rpm");
+    let base_dir = settings.project_out_directory().join("bundle/rpm");
+    let package_dir = base_dir.join(&package_base_name);
+
+    terminal::print_bundling(&package_name)?;
+
+    if package_dir.exists() {
+        fs::remove_dir_all(&package_dir)?;
+    }
+
+    let package_path = base_dir.join(package_name);
+
+    // Generate data files.
+    let data_dir = package_dir.join("data");
+    let binary_dest = data_dir.join("usr/bin").join(settings.binary_name());
+    file::copy(settings.binary_path(), &binary_dest)?;
+
+    transfer_resource_files(settings, &data_dir)?;
+    generate_icon_files(settings, &data_dir)?;
+    generate_desktop_file(settings, &data_dir)?;
+
+    // Write the payload, then the headers describing it.
+    let build_time = u32::try_from(settings.timestamp())
+        .map_err(|_| Error::InvalidSourceDateEpoch(settings.timestamp().to_string()))?;
+    let files = collect_files(settings, &data_dir)?;
+    let payload_path = package_dir.join("payload.cpio.gz");
+    let payload_size = create_payload(&files, build_time, &payload_path)?;
+    let payload_digest = file::generate_sha256sum(&payload_path)?;
+
+    let package = PackageInfo {
+        name: &name,
+        version: &version,
+        release,
+        arch,
+    };
+    let header = generate_header(settings, &package, &files, build_time, &payload_digest)?;
+    let signature = generate_signature(&header, &payload_path, payload_size)?;
+
+    let mut package_file = file::create(&package_path)?;
+    package_file.write_all(&generate_lead(&package))?;
+    package_file.write_all(&signature)?;
+    package_file.write_all(&header)?;
+    io::copy(&mut File::open(&payload_path)?, &mut package_file)?;
+    package_file.flush()?;
+
+    Ok(vec![package_path])
+}
+
+// Identity of the package being built.
+struct PackageInfo<'a> {
+    name: &'a str,
+    version: &'a str,
+    release: &'a str,
+    arch: &'a str,
+}
+
+/// Checks that the version and release of the package are valid, so that
+/// `name-version-release` can be split back into its parts.
+pub(crate) fn check_version_parts(version: &str, release: &str) -> Result<(), Error> {
+    if !is_rpm_version_part(version) {
+        return Err(Error::InvalidRpmVersion("version", version.to_string()));
+    }
+    if !is_rpm_version_part(release) {
+        return Err(Error::InvalidRpmVersion("release", release.to_string()));
+    }
+    Ok(())
+}
+
+// Info about a file (or directory) installed by the package.
+struct FileInfo {
+    // The path to the staged copy of this file.
+    source_path: PathBuf,
+    // The absolute path where this file will be installed.
+    install_path: String,
+    // The file's type and permission bits.
+    mode: u32,
+    // The size of this file, in bytes (zero for directories).
+    size: u32,
+    // The hex MD5 digest of the file contents (empty for directories).
+    digest: String,
+}
+
+impl FileInfo {
+    fn is_dir(&self) -> bool {
+        self.mode & 0o170000 == 0o040000
+    }
+
+    fn dir_and_base_name(&self) -> (&str, &str) {
+        let split = self.install_path.rfind('/').unwrap() + 1;
+        self.install_path.split_at(split)
+    }
+}
+
+/// Collects the files under `data_dir`, sorted by install path as rpm
+/// requires.  Directories are only included (and thus owned by the package)
+/// when they live under the package's private resource directory; shared
+/// directories such as `/usr/bin` belong to the system.
+fn collect_files(settings: &bundle::Settings, data_dir: &Path) -> Result<Vec<FileInfo>, Error> {
+    let owned_dir = resource_dir(settings, data_dir);
+    let mut files = Vec::new();
+    for entry in WalkDir::new(data_dir) {
+        let entry = entry?;
+        let path = entry.path();
+        let is_dir = entry.file_type().is_dir();
+        if is_dir && !path.starts_with(&owned_dir) {
+            continue;
+        }
+        let rel_path = path.strip_prefix(data_dir).unwrap();
+        let rel_path = rel_path.to_str().ok_or_else(|| {
+            let msg = format!("Non-UTF-8 path: {rel_path:?}");
+            io::Error::new(io::ErrorKind::InvalidData, msg)
+        })?;
+        let install_path = format!("/{}", rel_path.replace('\\', "/"));
+        let file_info = if is_dir {
+            FileInfo {
+                source_path: path.to_path_buf(),
+                install_path,
+                mode: 0o040755,
+                size: 0,
+                digest: String::new(),
+            }
+        } else {
+            let metadata = entry.metadata()?;
+            let mut digest = String::new();
+            for byte in generate_md5sum(path)?.iter() {
+                digest.push_str(&format!("{byte:02x}"));
+            }
+            FileInfo {
+                source_path: path.to_path_buf(),
+                install_path,
+                mode: 0o100000 | file_mode(&metadata),
+                size: to_u32(metadata.len())?,
+                digest,
+            }
+        };
+        files.push(file_info);
+    }
+    files.sort_by(|a, b| a.install_path.cmp(&b.install_path));
+    Ok(files)
+}
+
+/// Writes the gzipped cpio payload to `dest_path`, returning the uncompressed
+/// size of the archive.
+fn create_payload(files: &[FileInfo], mtime: u32, dest_path: &Path) -> Result<u32, Error> {
+    let dest_file = file::create(dest_path)?;
+    let gzip_encoder = gzip_encoder(dest_file, u64::from(mtime))?;
+    let mut cpio = CpioWriter {
+        inner: gzip_encoder,
+        written: 0,
+    };
+    for (index, file_info) in files.iter().enumerate() {
+        let name = format!(".{}", file_info.install_path);
+        let header = CpioHeader {
+            ino: index as u32 + 1,
+            mode: file_info.mode,
+            nlink: if file_info.is_dir() { 2 } else { 1 },
+            mtime,
+            size: file_info.size,
+        };
+        cpio.write_header(&header, &name)?;
+        if !file_info.is_dir() {
+            let copied = io::copy(&mut File::open(&file_info.source_path)?, &mut cpio)?;
+            debug_assert_eq!(copied, u64::from(file_info.size));
+            cpio.pad()?;
+        }
+    }
+    let trailer = CpioHeader {
+        ino: 0,
+        mode: 0,
+        nlink: 1,
+        mtime: 0,
+        size: 0,
+    };
+    cpio.write_header(&trailer, "TRAILER!!!")?;
+    let written = to_u32(cpio.written)?;
+    let mut dest_file = cpio.inner.finish().into_result()?;
+    dest_file.flush()?;
+    Ok(written)
+}
+
+struct CpioHeader {
+    ino: u32,
+    mode: u32,
+    nlink: u32,
+    mtime: u32,
+    size: u32,
+}
+
+/// Writes a cpio archive in the "new ASCII" (`newc`) format, keeping track of
+/// the number of uncompressed bytes written.
+struct CpioWriter<W: Write> {
+    inner: W,
+    written: u64,
+}
+
+impl<W: Write> CpioWriter<W> {
+    fn write_header(&mut self, header: &CpioHeader, name: &str) -> io::Result<()> {
+        let fields = [
+            header.ino,
+            header.mode,
+            0, // uid
+            0, // gid
+            header.nlink,
+            header.mtime,
+            header.size,
+            0, // devmajor
+            0, // devminor
+            0, // rdevmajor
+            0, // rdevminor
+            name.len() as u32 + 1,
+            0, // check
+        ];
+        let mut buffer = String::from("070701");
+        for field in fields {
+            buffer.push_str(&format!("{field:08x}"));
+        }
+        buffer.push_str(name);
+        buffer.push('\0');
+        self.write_all(buffer.as_bytes())?;
+        self.pad()
+    }
+
+    /// Pads the archive with zeros up to the next multiple of four bytes.
+    fn pad(&mut self) -> io::Result<()> {
+        let padding = (4 - self.written % 4) % 4;
+        self.write_all(&[0; 3][..padding as usize])
+    }
+}
+
+impl<W: Write> Write for CpioWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn generate_lead(package: &PackageInfo) -> Vec<u8> {
+    let mut lead = Vec::with_capacity(96);
+    lead.extend_from_slice(&LEAD_MAGIC);
+    lead.extend_from_slice(&[3, 0]); // Format version 3.0
+    lead.extend_from_slice(&0u16.to_be_bytes()); // Binary package
+    lead.extend_from_slice(&0u16.to_be_bytes()); // Architecture (unused)
+    let full_name = format!("{}-{}-{}", package.name, package.version, package.release);
+    let mut name = [0u8; LEAD_NAME_LEN];
+    let len = full_name.len().min(LEAD_NAME_LEN - 1);
+    name[..len].copy_from_slice(&full_name.as_bytes()[..len]);
+    lead.extend_from_slice(&name);
+    lead.extend_from_slice(&LEAD_OS_LINUX.to_be_bytes());
+    lead.extend_from_slice(&LEAD_SIGNATURE_TYPE.to_be_bytes());
+    lead.extend_from_slice(&[0; 16]);
+    debug_assert_eq!(lead.len(), 96);
+    lead
+}
+
+fn generate_header(
+    settings: &bundle::Settings,
+    package: &PackageInfo,
+    files: &[FileInfo],
+    build_time: u32,
+    payload_digest: &str,
+) -> Result<Vec<u8>, Error> {
+    let mut header = Header::new(TAG_HEADERIMMUTABLE);
+    header.add(TAG_HEADERI18NTABLE, Value::StringArray(vec!["C".into()]));
+    header.add(TAG_NAME, Value::String(package.name.into()));
+    header.add(TAG_VERSION, Value::String(package.version.into()));
+    header.add(TAG_RELEASE, Value::String(package.release.into()));
+
+    let mut summary = settings.short_description().trim();
+    if summary.is_empty() {
+        summary = "(none)";
+    }
+    let description = settings.long_description().unwrap_or(summary).trim();
+    header.add(TAG_SUMMARY, Value::I18nString(summary.into()));
+    header.add(TAG_DESCRIPTION, Value::I18nString(description.into()));
+    header.add(TAG_BUILDTIME, Value::Int32(vec![build_time]));
+    let total_size = files.iter().map(|f| u64::from(f.size)).sum();
+    header.add(TAG_SIZE, Value::Int32(vec![to_u32(total_size)?]));
+    if let Some(authors) = settings.authors_comma_separated() {
+        header.add(TAG_VENDOR, Value::String(authors));
+    }
+    let license = settings.license().unwrap_or("Unknown");
+    header.add(TAG_LICENSE, Value::String(license.into()));
+    header.add(TAG_GROUP, Value::I18nString("Unspecified".into()));
+    if !settings.homepage_url().is_empty() {
+        header.add(TAG_URL, Value::String(settings.homepage_url().into()));
+    }
+    header.add(TAG_OS, Value::String("linux".into()));
+    header.add(TAG_ARCH, Value::String(package.arch.into()));
+
+    // File list.  Directory names are stored once, and referenced by index
+    // from each file entry.
+    let mut dir_names: Vec<&str> = Vec::new();
+    let mut dir_indexes = Vec::new();
+    let mut base_names = Vec::new();
+    for file_info in files {
+        let (dir_name, base_name) = file_info.dir_and_base_name();
+        let index = match dir_names.iter().position(|&name| name == dir_name) {
+            Some(index) => index,
+            None => {
+                dir_names.push(dir_name);
+                dir_names.len() - 1
+            }
+        };
+        dir_indexes.push(index as u32);
+        base_names.push(base_name.to_string());
+    }
+    let count = files.len();
+    let file_sizes = files.iter().map(|f| f.size).collect();
+    let file_modes = files.iter().map(|f| f.mode as u16).collect();
+    let file_digests = files.iter().map(|f| f.digest.clone()).collect();
+    header.add(TAG_FILESIZES, Value::Int32(file_sizes));
+    header.add(TAG_FILEMODES, Value::Int16(file_modes));
+    header.add(TAG_FILERDEVS, Value::Int16(vec![0; count]));
+    header.add(TAG_FILEMTIMES, Value::Int32(vec![build_time; count]));
+    header.add(TAG_FILEDIGESTS, Value::StringArray(file_digests));
+    header.add(
+        TAG_FILELINKTOS,
+        Value::StringArray(vec![String::new(); count]),
+    );
+    header.add(TAG_FILEFLAGS, Value::Int32(vec![0; count]));
+    header.add(
+        TAG_FILEUSERNAME,
+        Value::StringArray(vec!["root".into(); count]),
+    );
+    header.add(
+        TAG_FILEGROUPNAME,
+        Value::StringArray(vec!["root".into(); count]),
+    );
+    let source_rpm = format!(
+        "{}-{}-{}.src.rpm",
+        package.name, package.version, package.release
+    );
+    header.add(TAG_SOURCERPM, Value::String(source_rpm));
+    header.add(TAG_FILEVERIFYFLAGS, Value::Int32(vec![u32::MAX; count]));
+
+    // Dependencies.
+    let full_version = format!("{}-{}", package.version, package.release);
+    header.add(
+        TAG_PROVIDENAME,
+        Value::StringArray(vec![package.name.into()]),
+    );
+    let rpmlib_requires = [
+        ("rpmlib(CompressedFileNames)", "3.0.4-1"),
+        ("rpmlib(PayloadFilesHavePrefix)", "4.0-1"),
+    ];
+    let require_flags = SENSE_LESS | SENSE_EQUAL | SENSE_RPMLIB;
+    header.add(
+        TAG_REQUIREFLAGS,
+        Value::Int32(vec![require_flags; rpmlib_requires.len()]),
+    );
+    header.add(
+        TAG_REQUIRENAME,
+        Value::StringArray(rpmlib_requires.iter().map(|r| r.0.into()).collect()),
+    );
+    header.add(
+        TAG_REQUIREVERSION,
+        Value::StringArray(rpmlib_requires.iter().map(|r| r.1.into()).collect()),
+    );
+    header.add(TAG_FILEDEVICES, Value::Int32(vec![1; count]));
+    header.add(TAG_FILEINODES, Value::Int32((1..=count as u32).collect()));
+    header.add(
+        TAG_FILELANGS,
+        Value::StringArray(vec![String::new(); count]),
+    );
+    header.add(TAG_PROVIDEFLAGS, Value::Int32(vec![SENSE_EQUAL]));
+    header.add(TAG_PROVIDEVERSION, Value::StringArray(vec![full_version]));
+    header.add(TAG_DIRINDEXES, Value::Int32(dir_indexes));
+    header.add(TAG_BASENAMES, Value::StringArray(base_names));
+    header.add(
+        TAG_DIRNAMES,
+        Value::StringArray(dir_names.into_iter().map(String::from).collect()),
+    );
+    header.add(TAG_PAYLOADFORMAT, Value::String("cpio".into()));
+    header.add(TAG_PAYLOADCOMPRESSOR, Value::String("gzip".into()));
+    header.add(TAG_PAYLOADFLAGS, Value::String("9".into()));
+    header.add(
+        TAG_PAYLOADDIGEST,
+        Value::StringArray(vec![payload_digest.into()]),
+    );
+    header.add(TAG_PAYLOADDIGESTALGO, Value::Int32(vec![HASH_ALGO_SHA256]));
+    Ok(header.to_bytes())
+}
+
+/// Generates the signature header (padded to a multiple of eight bytes), which
+/// holds the size and digests of the header and payload.
+fn generate_signature(
+    header: &[u8],
+    payload_path: &Path,
+    payload_size: u32,
+) -> Result<Vec<u8>, Error> {
+    let mut md5 = md5::Context::new();
+    md5.consume(header);
+    io::copy(&mut File::open(payload_path)?, &mut md5)?;
+    let compressed_size = payload_path.metadata()?.len();
+
+    let mut signature = Header::new(SIGTAG_HEADERSIGNATURES);
+    signature.add(SIGTAG_SHA256, Value::String(sha256_hex(header)));
+    let total_size = header.len() as u64 + compressed_size;
+    signature.add(SIGTAG_SIZE, Value::Int32(vec![to_u32(total_size)?]));
+    signature.add(SIGTAG_MD5, Value::Bin(md5.compute().to_vec()));
+    signature.add(SIGTAG_PAYLOADSIZE, Value::Int32(vec![payload_size]));
+
+    let mut bytes = signature.to_bytes();
+    bytes.resize(bytes.len().next_multiple_of(8), 0);
+    Ok(bytes)
+}
+
+// The data stored in a header entry.
+enum Value {
+    Int16(Vec<u16>),
+    Int32(Vec<u32>),
+    String(String),
+    Bin(Vec<u8>),
+    StringArray(Vec<String>),
+    I18nString(String),
+}
+
+impl Value {
+    fn type_and_count(&self) -> (u32, usize) {
+        match self {
+            Value::Int16(values) => (TYPE_INT16, values.len()),
+            Value::Int32(values) => (TYPE_INT32, values.len()),
+            Value::String(_) => (TYPE_STRING, 1),
+            Value::Bin(bytes) => (TYPE_BIN, bytes.len()),
+            Value::StringArray(strings) => (TYPE_STRING_ARRAY, strings.len()),
+            Value::I18nString(_) => (TYPE_I18NSTRING, 1),
+        }
+    }
+
+    fn alignment(&self) -> usize {
+        match self {
+            Value::Int16(_) => 2,
+            Value::Int32(_) => 4,
+            _ => 1,
+        }
+    }
+
+    fn write_to(&self, data: &mut Vec<u8>) {
+        fn push_str(data: &mut Vec<u8>, string: &str) {
+            data.extend_from_slice(string.as_bytes());
+            data.push(0);
+        }
+        match self {
+            Value::Int16(values) => values
+                .iter()
+                .for_each(|v| data.extend_from_slice(&v.to_be_bytes())),
+            Value::Int32(values) => values
+                .iter()
+                .for_each(|v| data.extend_from_slice(&v.to_be_bytes())),
+            Value::String(string) | Value::I18nString(string) => push_str(data, string),
+            Value::Bin(bytes) => data.extend_from_slice(bytes),
+            Value::StringArray(strings) => strings.iter().for_each(|s| push_str(data, s)),
+        }
+    }
+}
+
+/// An RPM header structure: an index of tagged entries, followed by the data
+/// store they point into.  Entries are wrapped in an immutable region, as
+/// `rpmbuild` does for both the signature and the main header.
+struct Header {
+    region_tag: u32,
+    entries: Vec<(u32, Value)>,
+}
+
+impl Header {
+    fn new(region_tag: u32) -> Header {
+        Header {
+            region_tag,
+            entries: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, tag: u32, value: Value) {
+        self.entries.push((tag, value));
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut entries: Vec<&(u32, Value)> = self.entries.iter().collect();
+        entries.sort_by_key(|(tag, _)| *tag);
+
+        let mut index = Vec::new();
+        let mut data = Vec::new();
+        for (tag, value) in entries {
+            data.resize(data.len().next_multiple_of(value.alignment()), 0);
+            let (value_type, count) = value.type_and_count();
+            push_index_entry(
+                &mut index,
+                *tag,
+                value_type,
+                data.len() as u32,
+                count as u32,
+            );
+            value.write_to(&mut data);
+        }
+
+        // The region trailer is an index entry stored at the end of the data,
+        // whose (negative) offset spans every index entry in the region.
+        let entry_count = self.entries.len() as u32 + 1;
+        let trailer_offset = data.len() as u32;
+        let region_size = (entry_count * 16).wrapping_neg();
+        push_index_entry(&mut data, self.region_tag, TYPE_BIN, region_size, 16);
+        let mut region = Vec::new();
+        push_index_entry(&mut region, self.region_tag, TYPE_BIN, trailer_offset, 16);
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&HEADER_MAGIC);
+        bytes.extend_from_slice(&entry_count.to_be_bytes());
+        bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&region);
+        bytes.extend_from_slice(&index);
+        bytes.extend_from_slice(&data);
+        bytes
+    }
+}
+
+fn push_index_entry(buffer: &mut Vec<u8>, tag: u32, value_type: u32, offset: u32, count: u32) {
+    for field in [tag, value_type, offset, count] {
+        buffer.extend_from_slice(&field.to_be_bytes());
+    }
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn to_u32(size: u64) -> Result<u32, Error> {
+    u32::try_from(size).map_err(|_| {
+        let msg = format!("{size} bytes is too large for an rpm package");
+        Error::from(io::Error::new(io::ErrorKind::InvalidData, msg))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn header_has_immutable_region() {
+        let mut header = Header::new(TAG_HEADERIMMUTABLE);
+        header.add(TAG_VERSION, Value::String("1.0".into()));
+        header.add(TAG_NAME, Value::String("foo".into()));
+        header.add(TAG_FILEMODES, Value::Int16(vec![0o100755]));
+        header.add(TAG_SIZE, Value::Int32(vec![42]));
+        let bytes = header.to_bytes();
+
+        assert_eq!(&bytes[..8], &HEADER_MAGIC);
+        let entry_count = read_u32(&bytes, 8) as usize;
+        let data_len = read_u32(&bytes, 12) as usize;
+        assert_eq!(entry_count, 5);
+        let data_start = 16 + entry_count * 16;
+        assert_eq!(bytes.len(), data_start + data_len);
+
+        // The region entry comes first and points at the trailer, which is
+        // the last sixteen bytes of the data store.
+        assert_eq!(read_u32(&bytes, 16), TAG_HEADERIMMUTABLE);
+        assert_eq!(read_u32(&bytes, 24) as usize, data_len - 16);
+        let trailer = data_start + data_len - 16;
+        assert_eq!(read_u32(&bytes, trailer), TAG_HEADERIMMUTABLE);
+        assert_eq!(read_u32(&bytes, trailer + 8) as i32, -(5 * 16));
+
+        // The remaining entries are sorted by tag, with aligned offsets.
+        let tags: Vec<u32> = (1..entry_count)
+            .map(|i| read_u32(&bytes, 16 + i * 16))
+            .collect();
+        assert_eq!(tags, vec![TAG_NAME, TAG_VERSION, TAG_SIZE, TAG_FILEMODES]);
+        let size_offset = read_u32(&bytes, 16 + 3 * 16 + 8) as usize;
+        assert_eq!(size_offset % 4, 0);
+        assert_eq!(read_u32(&bytes, data_start + size_offset), 42);
+        assert_eq!(&bytes[data_start..data_start + 4], b"foo\0");
+    }
+
+    #[test]
+    fn cpio_entries_are_padded() {
+        let mut cpio = CpioWriter {
+            inner: Vec::new(),
+            written: 0,
+        };
+        let header = CpioHeader {
+            ino: 1,
+            mode: 0o100644,
+            nlink: 1,
+            mtime: 0,
+            size: 5,
+        };
+        cpio.write_header(&header, "./a").unwrap();
+        cpio.write_all(b"hello").unwrap();
+        cpio.pad().unwrap();
+
+        let bytes = cpio.inner;
+        assert_eq!(&bytes[..6], b"070701");
+        assert_eq!(&bytes[6..14], b"00000001");
+        assert_eq!(&bytes[14..22], b"000081a4");
+        assert_eq!(&bytes[110..114], b"./a\0");
+        assert_eq!(&bytes[116..121], b"hello");
+        assert_eq!(bytes.len(), 124);
+        assert_eq!(cpio.written, 124);
+    }
+
+    #[test]
+    fn lead_is_96_bytes() {
+        let package = PackageInfo {
+            name: "foo",
+            version: "1.0",
+            release: "1",
+            arch: "x86_64",
+        };
+        let lead = generate_lead(&package);
+        assert_eq!(lead.len(), 96);
+        assert_eq!(&lead[..4], &LEAD_MAGIC);
+        assert_eq!(&lead[10..19], b"foo-1.0-1");
+        assert_eq!(lead[19], 0);
+    }
 }
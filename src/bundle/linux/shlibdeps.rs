@@ -0,0 +1,528 @@
+// Works out the `Depends` of a `.deb` package from the shared libraries its
+// binary links to, much like `dpkg-shlibdeps` does:
+//
+// * The sonames of the libraries are the `DT_NEEDED` entries of the binary's
+//   dynamic section, and the symbols it uses from each are its undefined
+//   dynamic symbols, named `symbol@VERSION` (or `symbol@Base` if
+//   unversioned).
+// * The package containing each library is found in the dpkg database's
+//   `info/<package>.list` files, which list the files of each installed
+//   package.
+// * The package's `info/<package>.symbols` file gives the dependency on it
+//   for each of its libraries, with the version that introduced each symbol,
+//   so the minimum version is the newest of the symbols used.  Failing that,
+//   its `info/<package>.shlibs` file gives the dependency for each library.
+//
+// See https://manpages.debian.org/deb-symbols and
+// https://manpages.debian.org/deb-shlibs for the formats of these files.
+
+use crate::bundle;
+use crate::bundle::version::compare_debian_versions;
+use crate::Error;
+
+use goblin::elf::Elf;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A shared library needed by a binary.
+#[derive(Debug)]
+struct NeededLibrary {
+    /// The soname of the library, e.g. `libc.so.6`.
+    soname: String,
+    /// The symbols used from the library, as `symbol@VERSION`.
+    symbols: HashSet<String>,
+}
+
+/// Returns the `Depends` of the `.deb` package: the `deb_depends` setting
+/// and, if `deb_shlibdeps` is set, the packages containing the shared
+/// libraries the binary links to.  Where both name a package, the one in
+/// `deb_depends` is used.
+pub(crate) fn depends(settings: &bundle::Settings, arch: &str) -> Result<Vec<String>, Error> {
+    let mut depends = settings.debian_dependencies().to_vec();
+    if settings.deb_shlibdeps() {
+        let binary = fs::read(settings.binary_path())?;
+        let libraries = needed_libraries(&binary)?;
+        let no_overrides = BTreeMap::new();
+        let overrides = settings.deb_shlibdeps_overrides().unwrap_or(&no_overrides);
+        let detected = library_relations(&libraries, settings.deb_dpkg_dir(), arch, overrides)?;
+        merge_relations(&mut depends, detected);
+    }
+    Ok(depends)
+}
+
+/// Returns the shared libraries needed by the ELF `binary`, in the order of
+/// its `DT_NEEDED` entries.
+fn needed_libraries(binary: &[u8]) -> Result<Vec<NeededLibrary>, Error> {
+    let elf = Elf::parse(binary)?;
+    let mut libraries: Vec<NeededLibrary> = elf
+        .libraries
+        .iter()
+        .map(|soname| NeededLibrary {
+            soname: soname.to_string(),
+            symbols: HashSet::new(),
+        })
+        .collect();
+    // The library and name of each symbol version, by version index.
+    let mut versions = HashMap::new();
+    for need in elf.verneed.iter().flat_map(|verneed| verneed.iter()) {
+        let Some(file) = elf.dynstrtab.get_at(need.vn_file) else {
+            continue;
+        };
+        for aux in need.iter() {
+            if let Some(name) = elf.dynstrtab.get_at(aux.vna_name) {
+                versions.insert(aux.vna_other, (file, name));
+            }
+        }
+    }
+    for (index, symbol) in elf.dynsyms.iter().enumerate() {
+        if !symbol.is_import() {
+            continue;
+        }
+        let Some(name) = elf
+            .dynstrtab
+            .get_at(symbol.st_name)
+            .filter(|name| !name.is_empty())
+        else {
+            continue;
+        };
+        let version = elf
+            .versym
+            .as_ref()
+            .and_then(|versym| versym.get_at(index))
+            .and_then(|versym| versions.get(&versym.version()));
+        match version {
+            Some((file, version)) => {
+                if let Some(library) = libraries.iter_mut().find(|lib| lib.soname == *file) {
+                    library.symbols.insert(format!("{name}@{version}"));
+                }
+            }
+            // An unversioned symbol could come from any of the libraries.
+            None => {
+                for library in &mut libraries {
+                    library.symbols.insert(format!("{name}@Base"));
+                }
+            }
+        }
+    }
+    Ok(libraries)
+}
+
+/// Returns the relations on the packages containing `libraries`, according
+/// to the dpkg database in `dpkg_dir`, for a package for the Debian
+/// architecture `arch`.  `overrides` gives the relation to use for a soname
+/// instead, or an empty string for none.
+fn library_relations(
+    libraries: &[NeededLibrary],
+    dpkg_dir: &Path,
+    arch: &str,
+    overrides: &BTreeMap<String, String>,
+) -> Result<Vec<String>, Error> {
+    let info_dir = dpkg_dir.join("info");
+    let sonames: HashSet<&str> = libraries
+        .iter()
+        .map(|library| library.soname.as_str())
+        .filter(|soname| !overrides.contains_key(*soname))
+        .collect();
+    let owners = library_owners(&info_dir, &sonames, arch)?;
+    let mut relations = Vec::new();
+    for library in libraries {
+        if let Some(relation) = overrides.get(&library.soname) {
+            relations.extend(split_relations(relation));
+            continue;
+        }
+        let owner = owners.get(library.soname.as_str()).ok_or_else(|| {
+            Error::SharedLibraryNotFound(library.soname.clone(), dpkg_dir.to_path_buf())
+        })?;
+        let package = owner.split(':').next().unwrap();
+        let symbols_path = info_dir.join(format!("{owner}.symbols"));
+        let shlibs_path = info_dir.join(format!("{owner}.shlibs"));
+        match read_optional(&symbols_path)?.and_then(|symbols| symbols_relations(&symbols, library))
+        {
+            Some(library_relations) => relations.extend(library_relations),
+            None => match read_optional(&shlibs_path)?
+                .and_then(|shlibs| shlibs_relations(&shlibs, &library.soname))
+            {
+                Some(library_relations) => relations.extend(library_relations),
+                None => relations.push(package.to_string()),
+            },
+        }
+    }
+    Ok(relations)
+}
+
+/// Returns the packages containing each of `sonames`, as the names of their
+/// `.list` files in `info_dir` without the extension (e.g. `libc6:amd64`).
+/// Packages for the architecture `arch` are preferred over the rest.
+fn library_owners(
+    info_dir: &Path,
+    sonames: &HashSet<&str>,
+    arch: &str,
+) -> Result<HashMap<String, String>, Error> {
+    let mut owners = HashMap::new();
+    if sonames.is_empty() {
+        return Ok(owners);
+    }
+    let entries = fs::read_dir(info_dir).map_err(|error| match error.kind() {
+        io::ErrorKind::NotFound => Error::DpkgDatabaseNotFound(info_dir.parent().unwrap().into()),
+        _ => error.into(),
+    })?;
+    let mut lists = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "list")
+        {
+            let stem = path.file_stem().unwrap().to_string_lossy().into_owned();
+            lists.push((stem, path));
+        }
+    }
+    // Packages for this architecture first, then packages with no
+    // architecture in their name, then the rest.
+    let rank = |stem: &str| match stem.split_once(':') {
+        Some((_, list_arch)) if list_arch == arch => 0,
+        None => 1,
+        Some(_) => 2,
+    };
+    lists.sort_by(|(a, _), (b, _)| rank(a).cmp(&rank(b)).then_with(|| a.cmp(b)));
+    for (stem, path) in lists {
+        for line in fs::read_to_string(&path)?.lines() {
+            let Some(file_name) = Path::new(line).file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if sonames.contains(file_name) && !owners.contains_key(file_name) {
+                owners.insert(file_name.to_string(), stem.clone());
+            }
+        }
+        if owners.len() == sonames.len() {
+            break;
+        }
+    }
+    Ok(owners)
+}
+
+/// Returns the relations given by the `symbols` file for `library`, or `None`
+/// if it doesn't cover the library.
+fn symbols_relations(symbols: &str, library: &NeededLibrary) -> Option<Vec<String>> {
+    // The dependency templates of the library (the main one, then the
+    // alternatives after `|`), with the minimum version of each, if any of
+    // its symbols are used.
+    let mut templates: Vec<(&str, Option<Option<&str>>)> = Vec::new();
+    for line in symbols.lines() {
+        if line.starts_with([' ', '\t']) {
+            if templates.is_empty() {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let (Some(symbol), Some(min_version)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            // Skip tags like `(optional)` or `(arch=amd64)`.
+            let mut symbol = symbol;
+            while let Some(rest) = symbol.strip_prefix('(') {
+                symbol = rest.split_once(')').map_or("", |(_, rest)| rest);
+            }
+            let id: usize = fields.next().and_then(|id| id.parse().ok()).unwrap_or(0);
+            if !library.symbols.contains(symbol) {
+                continue;
+            }
+            let Some((_, used)) = templates.get_mut(id) else {
+                continue;
+            };
+            // A minimum version of 0 means any version of the package has
+            // the symbol.
+            let min_version = Some(min_version).filter(|version| *version != "0");
+            *used = Some(match (used.flatten(), min_version) {
+                (Some(newest), Some(version)) => Some(std::cmp::max_by(newest, version, |a, b| {
+                    compare_debian_versions(a, b)
+                })),
+                (newest, version) => newest.or(version),
+            });
+        } else if let Some(template) = line.strip_prefix('|') {
+            if !templates.is_empty() {
+                templates.push((template.trim(), None));
+            }
+        } else if line.starts_with(['*', '#']) || line.trim().is_empty() {
+            continue;
+        } else if !templates.is_empty() {
+            break;
+        } else if let Some((soname, template)) = line.split_once(char::is_whitespace) {
+            if soname == library.soname {
+                templates.push((template.trim(), Some(None)));
+            }
+        }
+    }
+    if templates.is_empty() {
+        return None;
+    }
+    let mut relations = Vec::new();
+    for (template, used) in templates {
+        let Some(min_version) = used else {
+            continue;
+        };
+        let min_version = min_version
+            .map(|version| format!("(>= {version})"))
+            .unwrap_or_default();
+        relations.extend(split_relations(&template.replace("#MINVER#", &min_version)));
+    }
+    Some(relations)
+}
+
+/// Returns the relations given by the `shlibs` file for the library named
+/// `soname`, or `None` if it doesn't cover the library.
+fn shlibs_relations(shlibs: &str, soname: &str) -> Option<Vec<String>> {
+    let (name, version) = split_soname(soname)?;
+    shlibs.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let library = fields.next()?;
+        // Skip comments and entries for other package types, like `udeb:`.
+        if library.starts_with('#') || library.ends_with(':') {
+            return None;
+        }
+        if library != name || fields.next()? != version {
+            return None;
+        }
+        Some(split_relations(&fields.collect::<Vec<_>>().join(" ")))
+    })
+}
+
+/// Splits a soname into the library name and version used in `shlibs` files:
+/// `libfoo.so.6` is `libfoo` version `6`, and `libfoo-1.2.so` is `libfoo`
+/// version `1.2`.
+fn split_soname(soname: &str) -> Option<(&str, &str)> {
+    if let Some(split) = soname.split_once(".so.") {
+        return Some(split);
+    }
+    let (name, version) = soname.strip_suffix(".so")?.rsplit_once('-')?;
+    version
+        .starts_with(|c: char| c.is_ascii_digit())
+        .then_some((name, version))
+}
+
+/// Splits a comma-separated list of relations.
+fn split_relations(relations: &str) -> Vec<String> {
+    relations
+        .split(',')
+        .map(|relation| relation.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|relation| !relation.is_empty())
+        .collect()
+}
+
+/// Returns the package and minimum version of a relation that is just a
+/// package, optionally with a minimum version, e.g. `libc6 (>= 2.34)`.
+fn minimum_version(relation: &str) -> Option<(&str, Option<&str>)> {
+    match relation.split_once(" (>= ") {
+        Some((package, version)) => Some((package, Some(version.strip_suffix(')')?))),
+        None => (!relation.contains([' ', '(', '|'])).then_some((relation, None)),
+    }
+}
+
+/// Adds the `detected` relations to the `depends` given in the settings.
+/// Relations on a package named in `depends` are left out, and of those on
+/// the same package, only the one with the newest minimum version is kept.
+fn merge_relations(depends: &mut Vec<String>, detected: Vec<String>) {
+    let given = depends.len();
+    let named: HashSet<String> = depends
+        .iter()
+        .flat_map(|relation| relation.split('|'))
+        .filter_map(|package| {
+            let package = package.trim();
+            let end = package
+                .find([' ', '(', ':', '[', '<'])
+                .unwrap_or(package.len());
+            Some(package[..end].to_string()).filter(|package| !package.is_empty())
+        })
+        .collect();
+    for relation in detected {
+        let Some((package, version)) = minimum_version(&relation) else {
+            if !depends.contains(&relation) {
+                depends.push(relation);
+            }
+            continue;
+        };
+        if named.contains(package) {
+            continue;
+        }
+        let existing = depends[given..]
+            .iter_mut()
+            .find(|existing| minimum_version(existing).is_some_and(|(name, _)| name == package));
+        match existing {
+            Some(existing) => {
+                let newer = match (minimum_version(existing).unwrap().1, version) {
+                    (Some(existing_version), Some(version)) => {
+                        compare_debian_versions(version, existing_version).is_gt()
+                    }
+                    (existing_version, version) => existing_version.is_none() && version.is_some(),
+                };
+                if newer {
+                    *existing = relation;
+                }
+            }
+            None => depends.push(relation),
+        }
+    }
+}
+
+/// Returns the contents of the file at `path`, or `None` if it doesn't exist.
+fn read_optional(path: &Path) -> Result<Option<String>, Error> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{library_relations, merge_relations, split_soname, NeededLibrary};
+    use crate::Error;
+    use std::collections::BTreeMap;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn needed(soname: &str, symbols: &[&str]) -> NeededLibrary {
+        NeededLibrary {
+            soname: soname.to_string(),
+            symbols: symbols.iter().map(|symbol| symbol.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn relations_from_dpkg_database() {
+        let dpkg_dir = tempdir().unwrap();
+        let info_dir = dpkg_dir.path().join("info");
+        fs::create_dir(&info_dir).unwrap();
+        let files = [
+            ("libc6-i386.list", "/usr/lib32\n/usr/lib32/libc.so.6\n"),
+            (
+                "libc6:amd64.list",
+                "/usr/lib/x86_64-linux-gnu\n/usr/lib/x86_64-linux-gnu/libc.so.6\n",
+            ),
+            (
+                "libc6:amd64.symbols",
+                "libc.so.6 libc6 #MINVER#\n\
+                 | libc6 (>> 2.36), libc6 (<< 2.37)\n\
+                 * Build-Depends-Package: libc-dev\n \
+                 GLIBC_2.2.5@GLIBC_2.2.5 2.2.5\n \
+                 GLIBC_2.34@GLIBC_2.34 2.34\n \
+                 GLIBC_2.35@GLIBC_2.35 2.35\n \
+                 GLIBC_PRIVATE@GLIBC_PRIVATE 0 1\n \
+                 malloc@GLIBC_2.2.5 2.2.5\n \
+                 (arch=amd64)printf@GLIBC_2.2.5 2.2.5\n \
+                 pthread_create@GLIBC_2.34 2.34\n\
+                 libm.so.6 libc6 #MINVER#\n \
+                 sqrt@GLIBC_2.2.5 2.2.5\n",
+            ),
+            (
+                "libfoo1:amd64.list",
+                "/usr/lib/libfoo.so.1.4.2\n/usr/lib/libfoo.so.1\n",
+            ),
+            (
+                "libfoo1:amd64.shlibs",
+                "udeb: libfoo 1 libfoo1-udeb\nlibfoo 1 libfoo1 (>= 1.4)\n",
+            ),
+            (
+                "libbar:i386.list",
+                "/usr/lib/i386-linux-gnu/libbar-2.0.so\n",
+            ),
+        ];
+        for (name, contents) in files {
+            fs::write(info_dir.join(name), contents).unwrap();
+        }
+        let mut overrides = BTreeMap::new();
+        overrides.insert("libssl.so.3".to_string(), "libssl3 (>= 3.0.2)".to_string());
+        overrides.insert("libvendored.so".to_string(), String::new());
+
+        let libraries = [
+            needed("libc.so.6", &["malloc@GLIBC_2.2.5", "printf@GLIBC_2.2.5"]),
+            needed("libfoo.so.1", &[]),
+            needed("libbar-2.0.so", &[]),
+            needed("libssl.so.3", &[]),
+            needed("libvendored.so", &[]),
+        ];
+        assert_eq!(
+            library_relations(&libraries, dpkg_dir.path(), "amd64", &overrides).unwrap(),
+            vec![
+                "libc6 (>= 2.2.5)",
+                "libfoo1 (>= 1.4)",
+                "libbar",
+                "libssl3 (>= 3.0.2)",
+            ]
+        );
+
+        // The newest symbol gives the minimum version, and symbols for an
+        // alternative template add its relations.
+        let libraries = [needed(
+            "libc.so.6",
+            &[
+                "malloc@GLIBC_2.2.5",
+                "pthread_create@GLIBC_2.34",
+                "GLIBC_PRIVATE@GLIBC_PRIVATE",
+                "sqrt@GLIBC_2.2.5",
+            ],
+        )];
+        assert_eq!(
+            library_relations(&libraries, dpkg_dir.path(), "amd64", &overrides).unwrap(),
+            vec!["libc6 (>= 2.34)", "libc6 (>> 2.36)", "libc6 (<< 2.37)"]
+        );
+
+        let libraries = [needed("libmissing.so.2", &[])];
+        assert!(matches!(
+            library_relations(&libraries, dpkg_dir.path(), "amd64", &overrides),
+            Err(Error::SharedLibraryNotFound(soname, _)) if soname == "libmissing.so.2"
+        ));
+        assert!(matches!(
+            library_relations(&libraries, &info_dir, "amd64", &overrides),
+            Err(Error::DpkgDatabaseNotFound(_))
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn needed_libraries_of_elf_binary() {
+        let binary = fs::read(std::env::current_exe().unwrap()).unwrap();
+        let libraries = super::needed_libraries(&binary).unwrap();
+        let libc = libraries
+            .iter()
+            .find(|library| library.soname == "libc.so.6")
+            .unwrap();
+        assert!(libc.symbols.iter().any(|symbol| symbol.contains("@GLIBC_")));
+    }
+
+    #[test]
+    fn merged_relations() {
+        let mut depends = vec!["libfoo1 (>= 2.0) | libfoo2".to_string()];
+        merge_relations(
+            &mut depends,
+            vec![
+                "libc6 (>= 2.2.5)".to_string(),
+                "libfoo1 (>= 1.4)".to_string(),
+                "libc6 (>= 2.34)".to_string(),
+                "libgcc-s1 (>= 4.2)".to_string(),
+                "libc6".to_string(),
+                "libc6 (<< 2.37)".to_string(),
+                "libc6 (<< 2.37)".to_string(),
+            ],
+        );
+        assert_eq!(
+            depends,
+            vec![
+                "libfoo1 (>= 2.0) | libfoo2",
+                "libc6 (>= 2.34)",
+                "libgcc-s1 (>= 4.2)",
+                "libc6 (<< 2.37)",
+            ]
+        );
+    }
+
+    #[test]
+    fn soname_splitting() {
+        assert_eq!(split_soname("libc.so.6"), Some(("libc", "6")));
+        assert_eq!(split_soname("libfoo.so.1.2"), Some(("libfoo", "1.2")));
+        assert_eq!(split_soname("libfoo-1.2.so"), Some(("libfoo", "1.2")));
+        assert_eq!(split_soname("libfoo.so"), None);
+    }
+}
@@ -0,0 +1,583 @@
+// A minimal writer for SquashFS 4.0 images, as used for the filesystem
+// embedded in an AppImage.  An image is laid out like:
+//
+// superblock           # 96 bytes describing where everything else lives
+// data blocks          # The contents of each regular file, in 128 KiB blocks
+// inode table          # Metadata blocks holding one inode per file/directory
+// directory table      # Metadata blocks holding the directory listings
+// id table             # The uid/gid values referenced by inodes
+//
+// Data and metadata blocks are zlib-compressed (SquashFS's "gzip" compressor)
+// unless that would make them larger.  Fragments, extended attributes and the
+// NFS export table are not supported; every file is owned by root.  See
+// https://dr-emann.github.io/squashfs/ for a description of the format.
+
+use crate::bundle::linux::file_mode;
+use crate::Error;
+
+use libflate::zlib;
+use std::fs::{self, File};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: u32 = 0x7371_7368;
+const BLOCK_LOG: u16 = 17;
+const BLOCK_SIZE: usize = 1 << BLOCK_LOG;
+const METADATA_SIZE: usize = 8192;
+const SUPERBLOCK_SIZE: u64 = 96;
+// The image is padded to a multiple of this, so it can be used as a block
+// device.
+const DEVICE_BLOCK_SIZE: u64 = 4096;
+
+const COMPRESSION_GZIP: u16 = 1;
+const FLAG_NO_FRAGMENTS: u16 = 0x0010;
+const FLAG_NO_XATTRS: u16 = 0x0200;
+
+const METADATA_UNCOMPRESSED: u16 = 1 << 15;
+const DATA_UNCOMPRESSED: u32 = 1 << 24;
+const INVALID_FRAGMENT: u32 = 0xffff_ffff;
+const INVALID_XATTR: u32 = 0xffff_ffff;
+const INVALID_TABLE: u64 = 0xffff_ffff_ffff_ffff;
+
+const BASIC_DIR_TYPE: u16 = 1;
+const BASIC_FILE_TYPE: u16 = 2;
+const EXTENDED_DIR_TYPE: u16 = 8;
+const EXTENDED_FILE_TYPE: u16 = 9;
+
+// The most entries a single directory header may cover.
+const MAX_DIR_HEADER_ENTRIES: usize = 256;
+
+/// A file or directory to be written to the image.
+enum Node {
+    File {
+        name: String,
+        source_path: PathBuf,
+        mode: u16,
+        inode_number: u32,
+    },
+    Dir {
+        name: String,
+        children: Vec<Node>,
+        inode_number: u32,
+    },
+}
+
+impl Node {
+    fn name(&self) -> &str {
+        match self {
+            Node::File { name, .. } | Node::Dir { name, .. } => name,
+        }
+    }
+
+    fn inode_number(&self) -> u32 {
+        match self {
+            Node::File { inode_number, .. } | Node::Dir { inode_number, .. } => *inode_number,
+        }
+    }
+}
+
+// Where an inode was written, used to reference it from directory listings.
+struct InodeLocation {
+    name: String,
+    inode_type: u16,
+    inode_number: u32,
+    block: u32,
+    offset: u16,
+}
+
+/// Writes a SquashFS image containing the contents of `src_dir` to
+/// `dest_path`.  All entries are stamped with `mtime`; directories get mode
+/// 0755, and files get mode 0755 if any of their execute bits are set in
+/// `src_dir`, or 0644 otherwise.
+pub fn create_image(src_dir: &Path, dest_path: &Path, mtime: u32) -> Result<(), Error> {
+    let mut next_inode_number = 1;
+    let root = read_tree(src_dir, String::new(), &mut next_inode_number)?;
+    let inode_count = next_inode_number - 1;
+
+    let mut writer = ImageWriter {
+        image: File::create(dest_path)?,
+        position: SUPERBLOCK_SIZE,
+        inodes: MetadataWriter::new(),
+        directories: MetadataWriter::new(),
+        mtime,
+        root_parent: inode_count + 1,
+    };
+    writer.image.seek(SeekFrom::Start(SUPERBLOCK_SIZE))?;
+    let root_location = writer.write_node(&root, inode_count + 1)?;
+
+    let inode_table_start = writer.position;
+    let inode_table = writer.inodes.finish()?;
+    writer.write_all(&inode_table)?;
+    let directory_table_start = writer.position;
+    let directory_table = writer.directories.finish()?;
+    writer.write_all(&directory_table)?;
+
+    // No fragments are written, so the (empty) fragment table ends where it
+    // starts.  The id table holds a single id (0, for root), and is found via
+    // a list of pointers to its metadata blocks.
+    let fragment_table_start = writer.position;
+    let id_block_start = writer.position;
+    let mut ids = MetadataWriter::new();
+    ids.write(&0u32.to_le_bytes());
+    let id_block = ids.finish()?;
+    writer.write_all(&id_block)?;
+    let id_table_start = writer.position;
+    writer.write_all(&id_block_start.to_le_bytes())?;
+    let bytes_used = writer.position;
+
+    let padding = bytes_used.next_multiple_of(DEVICE_BLOCK_SIZE) - bytes_used;
+    writer.write_all(&vec![0; padding as usize])?;
+
+    let mut superblock = Vec::with_capacity(SUPERBLOCK_SIZE as usize);
+    superblock.extend_from_slice(&MAGIC.to_le_bytes());
+    superblock.extend_from_slice(&inode_count.to_le_bytes());
+    superblock.extend_from_slice(&mtime.to_le_bytes());
+    superblock.extend_from_slice(&(BLOCK_SIZE as u32).to_le_bytes());
+    superblock.extend_from_slice(&0u32.to_le_bytes()); // Fragment count
+    superblock.extend_from_slice(&COMPRESSION_GZIP.to_le_bytes());
+    superblock.extend_from_slice(&BLOCK_LOG.to_le_bytes());
+    superblock.extend_from_slice(&(FLAG_NO_FRAGMENTS | FLAG_NO_XATTRS).to_le_bytes());
+    superblock.extend_from_slice(&1u16.to_le_bytes()); // Id count
+    superblock.extend_from_slice(&4u16.to_le_bytes()); // Major version
+    superblock.extend_from_slice(&0u16.to_le_bytes()); // Minor version
+    superblock.extend_from_slice(&root_location.reference().to_le_bytes());
+    superblock.extend_from_slice(&bytes_used.to_le_bytes());
+    superblock.extend_from_slice(&id_table_start.to_le_bytes());
+    superblock.extend_from_slice(&INVALID_TABLE.to_le_bytes()); // Xattr table
+    superblock.extend_from_slice(&inode_table_start.to_le_bytes());
+    superblock.extend_from_slice(&directory_table_start.to_le_bytes());
+    superblock.extend_from_slice(&fragment_table_start.to_le_bytes());
+    superblock.extend_from_slice(&INVALID_TABLE.to_le_bytes()); // Export table
+    debug_assert_eq!(superblock.len() as u64, SUPERBLOCK_SIZE);
+    writer.image.seek(SeekFrom::Start(0))?;
+    writer.image.write_all(&superblock)?;
+    writer.image.flush()?;
+    Ok(())
+}
+
+/// Reads the directory tree at `path`, assigning inode numbers in the order
+/// the inodes will be written: children before their parent directory.
+fn read_tree(path: &Path, name: String, next_inode_number: &mut u32) -> Result<Node, Error> {
+    let mut entries = fs::read_dir(path)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    let mut children = Vec::new();
+    for entry in entries {
+        let child_name = entry.file_name().into_string().map_err(|name| {
+            let msg = format!("Non-UTF-8 path: {name:?}");
+            io::Error::new(io::ErrorKind::InvalidData, msg)
+        })?;
+        let child_path = entry.path();
+        if entry.file_type()?.is_dir() {
+            children.push(read_tree(&child_path, child_name, next_inode_number)?);
+        } else {
+            children.push(Node::File {
+                name: child_name,
+                mode: file_mode(&fs::metadata(&child_path)?) as u16,
+                source_path: child_path,
+                inode_number: *next_inode_number,
+            });
+            *next_inode_number += 1;
+        }
+    }
+    let inode_number = *next_inode_number;
+    *next_inode_number += 1;
+    Ok(Node::Dir {
+        name,
+        children,
+        inode_number,
+    })
+}
+
+struct ImageWriter {
+    image: File,
+    position: u64,
+    inodes: MetadataWriter,
+    directories: MetadataWriter,
+    mtime: u32,
+    root_parent: u32,
+}
+
+impl ImageWriter {
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.image.write_all(bytes)?;
+        self.position += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Writes the data and inode for `node` (and, for directories, all of its
+    /// descendants), returning where the inode was written.
+    fn write_node(&mut self, node: &Node, parent_inode: u32) -> Result<InodeLocation, Error> {
+        match node {
+            Node::File {
+                source_path, mode, ..
+            } => self.write_file(node, source_path, *mode),
+            Node::Dir { children, .. } => {
+                let mut locations = Vec::new();
+                for child in children {
+                    locations.push(self.write_node(child, node.inode_number())?);
+                }
+                let subdir_count = children
+                    .iter()
+                    .filter(|child| matches!(child, Node::Dir { .. }))
+                    .count() as u32;
+                self.write_dir(node, parent_inode, subdir_count, &locations)
+            }
+        }
+    }
+
+    fn write_file(
+        &mut self,
+        node: &Node,
+        source_path: &Path,
+        mode: u16,
+    ) -> Result<InodeLocation, Error> {
+        let contents = fs::read(source_path)?;
+        let blocks_start = self.position;
+        let mut block_sizes = Vec::new();
+        for chunk in contents.chunks(BLOCK_SIZE) {
+            let compressed = compress(chunk)?;
+            if compressed.len() < chunk.len() {
+                block_sizes.push(compressed.len() as u32);
+                self.write_all(&compressed)?;
+            } else {
+                block_sizes.push(chunk.len() as u32 | DATA_UNCOMPRESSED);
+                self.write_all(chunk)?;
+            }
+        }
+
+        let file_size = contents.len() as u64;
+        let (block, offset) = self.inodes.position();
+        let mut inode = Vec::new();
+        let inode_type = if blocks_start <= u64::from(u32::MAX) && file_size <= u64::from(u32::MAX)
+        {
+            push_inode_header(&mut inode, BASIC_FILE_TYPE, mode, self.mtime, node);
+            inode.extend_from_slice(&(blocks_start as u32).to_le_bytes());
+            inode.extend_from_slice(&INVALID_FRAGMENT.to_le_bytes());
+            inode.extend_from_slice(&0u32.to_le_bytes()); // Fragment offset
+            inode.extend_from_slice(&(file_size as u32).to_le_bytes());
+            BASIC_FILE_TYPE
+        } else {
+            push_inode_header(&mut inode, EXTENDED_FILE_TYPE, mode, self.mtime, node);
+            inode.extend_from_slice(&blocks_start.to_le_bytes());
+            inode.extend_from_slice(&file_size.to_le_bytes());
+            inode.extend_from_slice(&0u64.to_le_bytes()); // Sparse bytes
+            inode.extend_from_slice(&1u32.to_le_bytes()); // Link count
+            inode.extend_from_slice(&INVALID_FRAGMENT.to_le_bytes());
+            inode.extend_from_slice(&0u32.to_le_bytes()); // Fragment offset
+            inode.extend_from_slice(&INVALID_XATTR.to_le_bytes());
+            EXTENDED_FILE_TYPE
+        };
+        for size in block_sizes {
+            inode.extend_from_slice(&size.to_le_bytes());
+        }
+        self.inodes.write(&inode);
+
+        Ok(InodeLocation {
+            name: node.name().to_string(),
+            inode_type,
+            inode_number: node.inode_number(),
+            block,
+            offset,
+        })
+    }
+
+    fn write_dir(
+        &mut self,
+        node: &Node,
+        parent_inode: u32,
+        subdir_count: u32,
+        children: &[InodeLocation],
+    ) -> Result<InodeLocation, Error> {
+        let (listing_block, listing_offset) = self.directories.position();
+        let listing = directory_listing(children);
+        self.directories.write(&listing);
+
+        // The directory size includes three bytes for the implicit `.` and
+        // `..` entries.
+        let file_size = listing.len() as u32 + 3;
+        let link_count = 2 + subdir_count;
+        let parent_inode = if parent_inode > self.root_parent {
+            self.root_parent
+        } else {
+            parent_inode
+        };
+        let (block, offset) = self.inodes.position();
+        let mut inode = Vec::new();
+        let inode_type = if file_size <= u32::from(u16::MAX) {
+            push_inode_header(&mut inode, BASIC_DIR_TYPE, 0o755, self.mtime, node);
+            inode.extend_from_slice(&listing_block.to_le_bytes());
+            inode.extend_from_slice(&link_count.to_le_bytes());
+            inode.extend_from_slice(&(file_size as u16).to_le_bytes());
+            inode.extend_from_slice(&listing_offset.to_le_bytes());
+            inode.extend_from_slice(&parent_inode.to_le_bytes());
+            BASIC_DIR_TYPE
+        } else {
+            push_inode_header(&mut inode, EXTENDED_DIR_TYPE, 0o755, self.mtime, node);
+            inode.extend_from_slice(&link_count.to_le_bytes());
+            inode.extend_from_slice(&file_size.to_le_bytes());
+            inode.extend_from_slice(&listing_block.to_le_bytes());
+            inode.extend_from_slice(&parent_inode.to_le_bytes());
+            inode.extend_from_slice(&0u16.to_le_bytes()); // Index count
+            inode.extend_from_slice(&listing_offset.to_le_bytes());
+            inode.extend_from_slice(&INVALID_XATTR.to_le_bytes());
+            EXTENDED_DIR_TYPE
+        };
+        self.inodes.write(&inode);
+
+        Ok(InodeLocation {
+            name: node.name().to_string(),
+            inode_type,
+            inode_number: node.inode_number(),
+            block,
+            offset,
+        })
+    }
+}
+
+impl InodeLocation {
+    fn reference(&self) -> u64 {
+        (u64::from(self.block) << 16) | u64::from(self.offset)
+    }
+
+    /// The inode type recorded in directory entries, which is always the
+    /// basic variant.
+    fn basic_type(&self) -> u16 {
+        match self.inode_type {
+            EXTENDED_DIR_TYPE => BASIC_DIR_TYPE,
+            EXTENDED_FILE_TYPE => BASIC_FILE_TYPE,
+            other => other,
+        }
+    }
+}
+
+fn push_inode_header(inode: &mut Vec<u8>, inode_type: u16, mode: u16, mtime: u32, node: &Node) {
+    inode.extend_from_slice(&inode_type.to_le_bytes());
+    inode.extend_from_slice(&mode.to_le_bytes());
+    inode.extend_from_slice(&0u16.to_le_bytes()); // Uid index
+    inode.extend_from_slice(&0u16.to_le_bytes()); // Gid index
+    inode.extend_from_slice(&mtime.to_le_bytes());
+    inode.extend_from_slice(&node.inode_number().to_le_bytes());
+}
+
+/// Encodes a directory listing.  Entries are grouped under headers; a new
+/// header is started whenever an entry's inode lives in a different metadata
+/// block, or its inode number is too far from the header's to be stored as a
+/// 16-bit difference.
+fn directory_listing(children: &[InodeLocation]) -> Vec<u8> {
+    let mut listing = Vec::new();
+    let mut start = 0;
+    while start < children.len() {
+        let first = &children[start];
+        let mut end = start + 1;
+        while end < children.len()
+            && end - start < MAX_DIR_HEADER_ENTRIES
+            && children[end].block == first.block
+            && i16::try_from(i64::from(children[end].inode_number) - i64::from(first.inode_number))
+                .is_ok()
+        {
+            end += 1;
+        }
+        listing.extend_from_slice(&((end - start - 1) as u32).to_le_bytes());
+        listing.extend_from_slice(&first.block.to_le_bytes());
+        listing.extend_from_slice(&first.inode_number.to_le_bytes());
+        for child in &children[start..end] {
+            let difference = (i64::from(child.inode_number) - i64::from(first.inode_number)) as i16;
+            listing.extend_from_slice(&child.offset.to_le_bytes());
+            listing.extend_from_slice(&difference.to_le_bytes());
+            listing.extend_from_slice(&child.basic_type().to_le_bytes());
+            listing.extend_from_slice(&(child.name.len() as u16 - 1).to_le_bytes());
+            listing.extend_from_slice(child.name.as_bytes());
+        }
+        start = end;
+    }
+    listing
+}
+
+/// Accumulates data into a table of metadata blocks, each holding up to
+/// 8 KiB of (possibly compressed) data behind a two-byte length header.
+struct MetadataWriter {
+    output: Vec<u8>,
+    pending: Vec<u8>,
+    error: Option<io::Error>,
+}
+
+impl MetadataWriter {
+    fn new() -> MetadataWriter {
+        MetadataWriter {
+            output: Vec::new(),
+            pending: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Returns the offset of the current block from the start of the table,
+    /// and the offset of the next byte within the uncompressed block.
+    fn position(&self) -> (u32, u16) {
+        (self.output.len() as u32, self.pending.len() as u16)
+    }
+
+    fn write(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            let len = bytes.len().min(METADATA_SIZE - self.pending.len());
+            self.pending.extend_from_slice(&bytes[..len]);
+            bytes = &bytes[len..];
+            if self.pending.len() == METADATA_SIZE {
+                self.flush_block();
+            }
+        }
+    }
+
+    fn flush_block(&mut self) {
+        let block = std::mem::take(&mut self.pending);
+        match compress(&block) {
+            Ok(compressed) if compressed.len() < block.len() => {
+                self.output
+                    .extend_from_slice(&(compressed.len() as u16).to_le_bytes());
+                self.output.extend_from_slice(&compressed);
+            }
+            Ok(_) => {
+                let header = block.len() as u16 | METADATA_UNCOMPRESSED;
+                self.output.extend_from_slice(&header.to_le_bytes());
+                self.output.extend_from_slice(&block);
+            }
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    /// Flushes the last block and returns the encoded table.
+    fn finish(&mut self) -> io::Result<Vec<u8>> {
+        if !self.pending.is_empty() {
+            self.flush_block();
+        }
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => Ok(std::mem::take(&mut self.output)),
+        }
+    }
+}
+
+fn compress(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = zlib::Encoder::new(Vec::new())?;
+    encoder.write_all(data)?;
+    encoder.finish().into_result()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+    }
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
+
+    #[test]
+    fn image_superblock() {
+        let temp_dir = tempdir().unwrap();
+        let src_dir = temp_dir.path().join("AppDir");
+        fs::create_dir_all(src_dir.join("usr/bin")).unwrap();
+        fs::write(src_dir.join("usr/bin/foo"), vec![7; BLOCK_SIZE + 10]).unwrap();
+        fs::write(src_dir.join("AppRun"), "#!/bin/sh\n").unwrap();
+        let image_path = temp_dir.path().join("image.squashfs");
+        create_image(&src_dir, &image_path, 1234).unwrap();
+
+        let image = fs::read(&image_path).unwrap();
+        assert_eq!(image.len() as u64 % DEVICE_BLOCK_SIZE, 0);
+        assert_eq!(read_u32(&image, 0), MAGIC);
+        // Two files and three directories.
+        assert_eq!(read_u32(&image, 4), 5);
+        assert_eq!(read_u32(&image, 8), 1234);
+        assert_eq!(read_u32(&image, 12), BLOCK_SIZE as u32);
+        assert_eq!(read_u16(&image, 22), BLOCK_LOG);
+        assert_eq!(read_u16(&image, 28), 4);
+
+        let bytes_used = read_u64(&image, 40);
+        let id_table_start = read_u64(&image, 48);
+        let inode_table_start = read_u64(&image, 64);
+        let directory_table_start = read_u64(&image, 72);
+        assert!(SUPERBLOCK_SIZE < inode_table_start);
+        assert!(inode_table_start < directory_table_start);
+        assert!(directory_table_start < id_table_start);
+        assert_eq!(id_table_start + 8, bytes_used);
+        let id_block_start = read_u64(&image, id_table_start as usize);
+        assert!(id_block_start < id_table_start);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_modes_follow_execute_bits() {
+        use std::os::unix::fs::PermissionsExt;
+        let temp_dir = tempdir().unwrap();
+        let src_dir = temp_dir.path().join("AppDir");
+        fs::create_dir_all(src_dir.join("usr/lib")).unwrap();
+        fs::write(src_dir.join("usr/lib/helper.sh"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(
+            src_dir.join("usr/lib/helper.sh"),
+            fs::Permissions::from_mode(0o700),
+        )
+        .unwrap();
+        fs::write(src_dir.join("usr/lib/data.txt"), "data").unwrap();
+        fs::set_permissions(
+            src_dir.join("usr/lib/data.txt"),
+            fs::Permissions::from_mode(0o664),
+        )
+        .unwrap();
+
+        let mut next_inode_number = 1;
+        let root = read_tree(&src_dir, String::new(), &mut next_inode_number).unwrap();
+        let Node::Dir { children, .. } = &root else {
+            panic!("root is not a directory");
+        };
+        let Node::Dir { children, .. } = &children[0] else {
+            panic!("usr is not a directory");
+        };
+        let Node::Dir { children, .. } = &children[0] else {
+            panic!("usr/lib is not a directory");
+        };
+        let modes: Vec<(&str, u16)> = children
+            .iter()
+            .map(|child| match child {
+                Node::File { name, mode, .. } => (name.as_str(), *mode),
+                Node::Dir { name, .. } => panic!("{name} is not a file"),
+            })
+            .collect();
+        assert_eq!(modes, vec![("data.txt", 0o644), ("helper.sh", 0o755)]);
+    }
+
+    #[test]
+    fn metadata_blocks_span_boundaries() {
+        let mut metadata = MetadataWriter::new();
+        metadata.write(&[0; METADATA_SIZE - 4]);
+        assert_eq!(metadata.position(), (0, METADATA_SIZE as u16 - 4));
+        metadata.write(&[0; 8]);
+        let (block, offset) = metadata.position();
+        assert!(block > 0);
+        assert_eq!(offset, 4);
+    }
+
+    #[test]
+    fn directory_listing_headers() {
+        let children: Vec<InodeLocation> = (0..300)
+            .map(|i| InodeLocation {
+                name: format!("file{i}"),
+                inode_type: BASIC_FILE_TYPE,
+                inode_number: i + 1,
+                block: if i < 10 { 0 } else { 100 },
+                offset: 0,
+            })
+            .collect();
+        let listing = directory_listing(&children);
+        // The first header covers the ten entries in block 0.
+        assert_eq!(read_u32(&listing, 0), 9);
+        assert_eq!(read_u32(&listing, 4), 0);
+        assert_eq!(read_u32(&listing, 8), 1);
+    }
+}
@@ -1,38 +1,68 @@
+mod ios_bundle;
+mod lint;
 mod linux;
 mod msi_bundle;
 mod osx_bundle;
+mod version;
 
-use crate::bundle::linux::deb_bundle;
-use crate::terminal;
+use crate::build;
+use crate::bundle::linux::{appimage_bundle, deb_bundle, rpm_bundle};
+use crate::file;
 use crate::{Category, Error};
 
-use clap::ArgMatches;
-
 use cargo_metadata::MetadataCommand;
-use serde::Deserialize;
-use serde_json::Value;
+use cargo_platform::{Cfg, Platform};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{json, Value};
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::OsString;
 use std::fmt::Display;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use target_build_utils::TargetInfo;
 
+pub use self::lint::{lint, Diagnostic, Severity};
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Bundle {
     WindowsMsi,
     MacosApplication,
+    IosApplication,
     LinuxDeb,
+    LinuxRpm,
+    LinuxAppImage,
+}
+
+impl Serialize for Bundle {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.short_name())
+    }
 }
 
 impl Bundle {
-    pub const ALL: &'static [Self] = &[Self::WindowsMsi, Self::MacosApplication, Self::LinuxDeb];
+    pub const ALL: &'static [Self] = &[
+        Self::WindowsMsi,
+        Self::MacosApplication,
+        Self::IosApplication,
+        Self::LinuxDeb,
+        Self::LinuxRpm,
+        Self::LinuxAppImage,
+    ];
+
+    /// The `--format` value which selects every format supported by the
+    /// target OS.
+    pub const ALL_FOR_TARGET: &'static str = "all-for-target";
 
     pub fn from_short_name(name: &str) -> Result<Bundle, Error> {
         match name {
             "msi" => Ok(Bundle::WindowsMsi),
-            "app" => Ok(Bundle::MacosApplication),
+            "osx" | "app" => Ok(Bundle::MacosApplication),
+            "ios" => Ok(Bundle::IosApplication),
             "deb" => Ok(Bundle::LinuxDeb),
+            "rpm" => Ok(Bundle::LinuxRpm),
+            "appimage" => Ok(Bundle::LinuxAppImage),
             _ => Err(Error::UnsupportedBundle(name.to_owned())),
         }
     }
@@ -41,7 +71,22 @@ impl Bundle {
         match self {
             Bundle::WindowsMsi => "msi",
             Bundle::MacosApplication => "osx",
+            Bundle::IosApplication => "ios",
             Bundle::LinuxDeb => "deb",
+            Bundle::LinuxRpm => "rpm",
+            Bundle::LinuxAppImage => "appimage",
+        }
+    }
+
+    /// Returns the bundle formats that can be produced for the given target
+    /// OS, starting with the one produced when no format is requested.
+    pub fn all_for_os(os: &str) -> Result<&'static [Bundle], Error> {
+        match os {
+            "macos" => Ok(&[Bundle::MacosApplication]),
+            "ios" => Ok(&[Bundle::IosApplication]),
+            "linux" => Ok(&[Bundle::LinuxDeb, Bundle::LinuxRpm, Bundle::LinuxAppImage]),
+            "windows" => Ok(&[Bundle::WindowsMsi]),
+            os => Err(Error::OSNotSupported(os.to_owned())),
         }
     }
 }
@@ -49,7 +94,7 @@ impl Bundle {
 #[derive(Clone, Debug)]
 pub struct Settings {
     package: cargo_metadata::Package,
-    bundle: Option<Bundle>, // If `None`, use the default package type for this os
+    bundles: Vec<Bundle>, // If empty, use the default package type for this os
     target: Option<(String, TargetInfo)>,
     features: Option<String>,
     project_out_directory: PathBuf,
@@ -59,99 +104,353 @@ pub struct Settings {
     no_default_features: bool,
     binary_path: PathBuf,
     binary_name: String,
+    bin_target: String,
+    prebuilt_binary: bool,
+    bundle_table: Value,
+    config_table: Value,
+    variant: Option<String>,
+    timestamp: u64,
     bundle_settings: Metadata,
 }
 
-impl Settings {
-    pub fn new(current_dir: PathBuf, matches: &ArgMatches) -> Result<Self, Error> {
-        let bundle = match matches.value_of("format") {
-            Some(name) => Some(Bundle::from_short_name(name)?),
-            None => None,
-        };
+/// Creates the `Settings` for each package to be bundled, from options like
+/// those accepted by `cargo bundle` on the command line.
+///
+/// ```no_run
+/// use cargo_bundle::{Bundle, SettingsBuilder};
+///
+/// let settings = SettingsBuilder::new(".")
+///     .package("my-app")
+///     .profile("release")
+///     .format(Bundle::LinuxDeb)
+///     .build()?;
+/// # Ok::<(), cargo_bundle::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct SettingsBuilder {
+    current_dir: PathBuf,
+    packages: Vec<String>,
+    workspace: bool,
+    exclude: Vec<String>,
+    build_artifact: BuildArtifact,
+    profile: String,
+    target: Option<String>,
+    formats: Vec<Bundle>,
+    all_formats_for_target: bool,
+    features: Option<String>,
+    all_features: bool,
+    no_default_features: bool,
+    binaries: Vec<String>,
+    config: Vec<String>,
+    variant: Option<String>,
+}
 
-        let build_artifact = if let Some(bin) = matches.value_of("bin") {
-            BuildArtifact::Bin(bin.to_string())
-        } else if let Some(example) = matches.value_of("example") {
-            BuildArtifact::Example(example.to_string())
-        } else {
-            BuildArtifact::Main
-        };
+impl SettingsBuilder {
+    /// Starts building settings for the package(s) in the given directory,
+    /// which must be inside a cargo package or workspace.
+    pub fn new<P: Into<PathBuf>>(current_dir: P) -> SettingsBuilder {
+        SettingsBuilder {
+            current_dir: current_dir.into(),
+            packages: Vec::new(),
+            workspace: false,
+            exclude: Vec::new(),
+            build_artifact: BuildArtifact::Main,
+            profile: "dev".to_string(),
+            target: None,
+            formats: Vec::new(),
+            all_formats_for_target: false,
+            features: None,
+            all_features: false,
+            no_default_features: false,
+            binaries: Vec::new(),
+            config: Vec::new(),
+            variant: None,
+        }
+    }
 
-        let profile = if matches.is_present("release") {
-            "release".to_string()
-        } else if let Some(profile) = matches.value_of("profile") {
-            if profile == "debug" {
-                return Err(Error::DebugProfileIsReserved);
-            }
+    /// Bundles the named workspace member (like `--package`).  May be called
+    /// more than once.
+    pub fn package<S: Into<String>>(mut self, name: S) -> Self {
+        self.packages.push(name.into());
+        self
+    }
 
-            profile.to_string()
-        } else {
-            "dev".to_string()
-        };
+    /// Bundles every workspace member that has bundle metadata (like
+    /// `--workspace`).
+    pub fn workspace(mut self) -> Self {
+        self.workspace = true;
+        self
+    }
+
+    /// Leaves the named package out when bundling the whole workspace (like
+    /// `--exclude`).
+    pub fn exclude<S: Into<String>>(mut self, name: S) -> Self {
+        self.exclude.push(name.into());
+        self
+    }
+
+    /// Selects the binary or example to bundle (like `--bin` or `--example`).
+    pub fn artifact(mut self, build_artifact: BuildArtifact) -> Self {
+        self.build_artifact = build_artifact;
+        self
+    }
+
+    /// Selects the cargo profile to build with, e.g. `"release"`.  Defaults to
+    /// `"dev"`.
+    pub fn profile<S: Into<String>>(mut self, profile: S) -> Self {
+        self.profile = profile.into();
+        self
+    }
+
+    /// Builds and bundles for the given target triple instead of the host.
+    pub fn target<S: Into<String>>(mut self, triple: S) -> Self {
+        self.target = Some(triple.into());
+        self
+    }
+
+    /// Adds a bundle format to produce.  If no formats are added, the native
+    /// format of the target OS is produced.
+    pub fn format(mut self, bundle: Bundle) -> Self {
+        if !self.formats.contains(&bundle) {
+            self.formats.push(bundle);
+        }
+        self
+    }
+
+    /// Produces every bundle format supported by the target OS.
+    pub fn all_formats_for_target(mut self) -> Self {
+        self.all_formats_for_target = true;
+        self
+    }
+
+    /// Sets the crate features to build with, e.g. `"f1 f2"`.
+    pub fn features<S: Into<String>>(mut self, features: S) -> Self {
+        self.features = Some(features.into());
+        self
+    }
+
+    pub fn all_features(mut self) -> Self {
+        self.all_features = true;
+        self
+    }
+
+    pub fn no_default_features(mut self) -> Self {
+        self.no_default_features = true;
+        self
+    }
+
+    /// Bundles a prebuilt binary instead of building one (like `--binary`).
+    /// The value is either a path, or `<triple>=<path>` to only use the path
+    /// when bundling for that target.  May be called more than once.
+    pub fn binary<S: Into<String>>(mut self, binary: S) -> Self {
+        self.binaries.push(binary.into());
+        self
+    }
 
-        let all_features = matches.is_present("all-features");
-        let no_default_features = matches.is_present("no-default-features");
-        let target = match matches.value_of("target") {
-            Some(triple) => Some((triple.to_string(), TargetInfo::from_str(triple)?)),
+    /// Overrides a bundle setting (like `--config`).  The value is
+    /// `<key>=<value>`, where `<key>` is a dotted path into
+    /// `[package.metadata.bundle]` (e.g. `version` or `bin.foo.name`) and
+    /// `<value>` is a TOML value; anything that isn't a TOML string, boolean,
+    /// array or table is taken as a plain string.  Later overrides win.
+    pub fn config<S: Into<String>>(mut self, config: S) -> Self {
+        self.config.push(config.into());
+        self
+    }
+
+    /// Bundles the named variant of the application (like `--variant`), using
+    /// the settings in its `[package.metadata.bundle.variant.<name>]` table.
+    pub fn variant<S: Into<String>>(mut self, variant: S) -> Self {
+        self.variant = Some(variant.into());
+        self
+    }
+
+    /// Overrides bundle settings from `CARGO_BUNDLE_<KEY>` environment
+    /// variables, where `<KEY>` is the upper-case name of a setting, e.g.
+    /// `CARGO_BUNDLE_VERSION`.  Values are parsed as for `config`.
+    pub fn config_from_env(mut self) -> Self {
+        let mut vars: Vec<(String, String)> = std::env::vars()
+            .filter_map(|(name, value)| {
+                let key = name.strip_prefix("CARGO_BUNDLE_")?.to_lowercase();
+                SETTING_KEYS.contains(&key.as_str()).then_some((key, value))
+            })
+            .collect();
+        vars.sort();
+        for (key, value) in vars {
+            self.config.push(format!("{key}={value}"));
+        }
+        self
+    }
+
+    /// Loads the package metadata, and returns the settings for each selected
+    /// package (see `Settings::select_packages`).  Warnings about the packages
+    /// selected are ignored; use `build_with` to report them.
+    pub fn build(&self) -> Result<Vec<Settings>, Error> {
+        self.build_with(|_| Ok(()))
+    }
+
+    /// Like `build`, but calls `on_warning` with each warning about the
+    /// packages selected (such as a named package having no
+    /// `[package.metadata.bundle]` section).  An error returned by the
+    /// callback stops the build.
+    pub fn build_with<F>(&self, mut on_warning: F) -> Result<Vec<Settings>, Error>
+    where
+        F: FnMut(&str) -> Result<(), Error>,
+    {
+        if self.profile == "debug" {
+            return Err(Error::DebugProfileIsReserved);
+        }
+        let profile = self.profile.clone();
+        let build_artifact = self.build_artifact.clone();
+        let all_features = self.all_features;
+        let no_default_features = self.no_default_features;
+        let target = match self.target {
+            Some(ref triple) => Some((triple.clone(), TargetInfo::from_str(triple)?)),
             None => None,
         };
+        let mut bundles = self.formats.clone();
+        if self.all_formats_for_target {
+            for &bundle in Bundle::all_for_os(target_os(&target))? {
+                if !bundles.contains(&bundle) {
+                    bundles.push(bundle);
+                }
+            }
+        }
 
-        let features = matches.value_of("features").map(|features| features.into());
-        // TODO: support multiple packages?
-        let (bundle_settings, package) =
-            Settings::find_bundle_package(load_metadata(&current_dir)?)?;
-        let workspace_dir = Settings::get_workspace_dir(current_dir);
+        let features = self.features.clone();
+        let metadata = load_metadata(&self.current_dir)?;
+        let workspace_table = metadata.workspace_metadata.get("bundle").cloned();
+        if let Some(ref workspace_table) = workspace_table {
+            let manifest_path = metadata.workspace_root.join("Cargo.toml");
+            check_setting_keys(
+                workspace_table,
+                manifest_path.as_std_path(),
+                "workspace.metadata.bundle",
+            )?;
+        }
+        let packages = Settings::select_packages(metadata, self, &mut on_warning)?;
+        let workspace_dir = Settings::get_workspace_dir(self.current_dir.clone());
         let target_dir =
             Settings::get_target_dir(&workspace_dir, &target, &profile, &build_artifact);
-        let (bundle_settings, mut binary_name) = match build_artifact {
-            BuildArtifact::Main => {
-                if let Some(target) = package
-                    .targets
-                    .iter()
-                    .find(|target| target.kind.iter().any(|k| k == "bin"))
-                {
-                    (bundle_settings, target.name.clone())
-                } else {
-                    Err(Error::NoBinTargetFound(package.name.clone()))?
+        let timestamp = source_date_epoch()?;
+        let mut config_table = json!({});
+        for config in &self.config {
+            merge_tables(&mut config_table, &parse_config(config)?);
+        }
+        let mut target_cfgs = None;
+        let mut settings = Vec::new();
+        for (bundle_table, package) in packages {
+            check_setting_keys(
+                &bundle_table,
+                package.manifest_path.as_std_path(),
+                "package.metadata.bundle",
+            )?;
+            let bundle_table = match workspace_table {
+                Some(ref workspace_table) => {
+                    inherit_workspace_table(workspace_table, bundle_table)?
+                }
+                None => bundle_table,
+            };
+            serde_json::from_value::<Metadata>(bundle_table.clone())?.check_nested_tables()?;
+            let (mut bundle_table, mut binary_name) = match build_artifact {
+                BuildArtifact::Main => {
+                    if let Some(target) = package
+                        .targets
+                        .iter()
+                        .find(|target| target.kind.iter().any(|k| k == "bin"))
+                    {
+                        (bundle_table, target.name.clone())
+                    } else {
+                        Err(Error::NoBinTargetFound(package.name.clone()))?
+                    }
+                }
+                BuildArtifact::Bin(ref name) => (
+                    bundle_settings_from_table(&bundle_table, "bin", name)?,
+                    name.clone(),
+                ),
+                BuildArtifact::Example(ref name) => (
+                    bundle_settings_from_table(&bundle_table, "example", name)?,
+                    name.clone(),
+                ),
+            };
+            let mut config_table = match build_artifact {
+                BuildArtifact::Main => config_table.clone(),
+                BuildArtifact::Bin(ref name) => {
+                    bundle_settings_from_table(&config_table, "bin", name)?
+                }
+                BuildArtifact::Example(ref name) => {
+                    bundle_settings_from_table(&config_table, "example", name)?
+                }
+            };
+            for table in [&bundle_table, &config_table] {
+                if let Some(Value::Object(overrides)) = table.get("target") {
+                    if !overrides.is_empty() && target_cfgs.is_none() {
+                        target_cfgs = Some(TargetCfgs::new(&target)?);
+                    }
                 }
             }
-            BuildArtifact::Bin(ref name) => (
-                bundle_settings_from_table(&bundle_settings.bin, "bin", name)?,
-                name.clone(),
-            ),
-            BuildArtifact::Example(ref name) => (
-                bundle_settings_from_table(&bundle_settings.example, "example", name)?,
-                name.clone(),
-            ),
-        };
+            if let Some(ref cfgs) = target_cfgs {
+                apply_target_overrides(&mut bundle_table, cfgs)?;
+                apply_target_overrides(&mut config_table, cfgs)?;
+            }
+            if let Some(ref variant) = self.variant {
+                apply_variant(&mut bundle_table, variant, &package.name)?;
+                apply_config_variant(&mut config_table, variant);
+            }
+            let bundle_settings = settings_with_config(&bundle_table, &config_table)?;
 
-        let binary_extension = match bundle {
-            Some(x) => match x {
-                Bundle::MacosApplication | Bundle::LinuxDeb => "",
-                Bundle::WindowsMsi => ".exe",
-            },
-            None => "",
-        };
+            let bin_target = binary_name.clone();
+            if target_os(&target) == "windows" {
+                binary_name += ".exe";
+            }
+            // This is only a guess; once the binary is built, the path that
+            // cargo reports is used instead (see `set_built_executable`).
+            let binary_path = target_dir.join(&binary_name);
+            let binary_name = installed_binary_name(&binary_name, self.variant.as_deref());
+
+            settings.push(Settings {
+                package,
+                bundles: bundles.clone(),
+                target: target.clone(),
+                features: features.clone(),
+                build_artifact: build_artifact.clone(),
+                profile: profile.clone(),
+                all_features,
+                no_default_features,
+                project_out_directory: target_dir.clone(),
+                binary_path,
+                binary_name,
+                bin_target,
+                prebuilt_binary: false,
+                bundle_table,
+                config_table,
+                variant: self.variant.clone(),
+                timestamp,
+                bundle_settings,
+            });
+        }
 
-        binary_name += binary_extension;
-        let binary_path = target_dir.join(&binary_name);
-
-        Ok(Settings {
-            package,
-            bundle,
-            target,
-            features,
-            build_artifact,
-            profile,
-            all_features,
-            no_default_features,
-            project_out_directory: target_dir,
-            binary_path,
-            binary_name,
-            bundle_settings,
-        })
+        if !self.binaries.is_empty() {
+            if settings.len() > 1 {
+                return Err(Error::BinaryForMultiplePackages);
+            }
+            let triple = match target {
+                Some((ref triple, _)) => Some(triple.clone()),
+                None => host_triple(),
+            };
+            let binary_path =
+                select_binary(&self.binaries, triple.as_deref()).ok_or_else(|| {
+                    Error::BinaryNotGivenForTarget(triple.unwrap_or_else(|| "host".to_string()))
+                })?;
+            for settings in &mut settings {
+                settings.binary_path = binary_path.clone();
+                settings.prebuilt_binary = true;
+            }
+        }
+        Ok(settings)
     }
+}
 
+impl Settings {
     /*
         The target_dir where binaries will be compiled to by cargo can vary:
             - this directory is a member of a workspace project
@@ -160,7 +459,9 @@ impl Settings {
             - if the build is a 'release' or 'debug' build
 
         This function determines where 'target' dir is and suffixes it with 'release' or 'debug'
-        to determine where the compiled binary will be located.
+        to determine where the compiled binary will be located.  When cargo-bundle runs the build
+        itself, the location cargo reports for the binary is used instead, so this guess only
+        matters when the build is skipped (with CARGO_BUNDLE_SKIP_BUILD).
     */
     fn get_target_dir(
         project_root_dir: &Path,
@@ -217,26 +518,110 @@ impl Settings {
         current_dir
     }
 
-    fn find_bundle_package(
+    /// Returns the packages to bundle, along with their (unparsed)
+    /// `[package.metadata.bundle]` tables.  If
+    /// any packages were named, those workspace members are selected; when
+    /// bundling the whole workspace, every member with a
+    /// `[package.metadata.bundle]` section (other than those excluded) is
+    /// selected.
+    /// Otherwise, the first workspace member with a bundle section is
+    /// selected, falling back to the root package.
+    fn select_packages<F>(
         metadata: cargo_metadata::Metadata,
-    ) -> Result<(Metadata, cargo_metadata::Package), Error> {
-        for package_id in metadata.workspace_members.iter() {
-            let package = &metadata[package_id];
+        builder: &SettingsBuilder,
+        on_warning: &mut F,
+    ) -> Result<Vec<(Value, cargo_metadata::Package)>, Error>
+    where
+        F: FnMut(&str) -> Result<(), Error>,
+    {
+        let members: Vec<&cargo_metadata::Package> = metadata
+            .workspace_members
+            .iter()
+            .map(|package_id| &metadata[package_id])
+            .collect();
+
+        if !builder.packages.is_empty() {
+            let mut packages = Vec::new();
+            for name in &builder.packages {
+                let package = members
+                    .iter()
+                    .find(|package| package.name == *name)
+                    .ok_or_else(|| Error::PackageNotFound(name.clone()))?;
+                let settings = match package.metadata.get("bundle") {
+                    Some(bundle) => bundle.clone(),
+                    None => {
+                        on_warning(&format!(
+                            "Package '{name}' has no [package.metadata.bundle] section"
+                        ))?;
+                        json!({})
+                    }
+                };
+                packages.push((settings, (*package).clone()));
+            }
+            return Ok(packages);
+        }
+
+        if builder.workspace {
+            let mut packages = Vec::new();
+            for package in members {
+                if builder.exclude.contains(&package.name) {
+                    continue;
+                }
+                if let Some(bundle) = package.metadata.get("bundle") {
+                    packages.push((bundle.clone(), package.clone()));
+                }
+            }
+            if packages.is_empty() {
+                return Err(Error::NoBundlePackages);
+            }
+            return Ok(packages);
+        }
+
+        for package in members {
             if let Some(bundle) = package.metadata.get("bundle") {
-                let settings = serde_json::from_value::<Metadata>(bundle.clone())?;
-                return Ok((settings, package.clone()));
+                return Ok(vec![(bundle.clone(), package.clone())]);
             }
         }
 
-        terminal::print_warning("No package in workspace has [package.metadata.bundle] section")?;
+        on_warning("No package in workspace has [package.metadata.bundle] section")?;
 
         if let Some(root_package) = metadata.root_package() {
-            Ok((Metadata::default(), root_package.clone()))
+            Ok(vec![(json!({}), root_package.clone())])
         } else {
             Err(Error::RootPackageNotFound)
         }
     }
 
+    /// Returns the settings to use when creating the given kind of bundle,
+    /// with any `[package.metadata.bundle.format.<format>]` table merged over
+    /// the other settings.
+    pub fn for_bundle(&self, bundle: Bundle) -> Result<Settings, Error> {
+        let mut settings = self.clone();
+        apply_format_overrides(&mut settings.bundle_table, bundle)?;
+        apply_format_overrides(&mut settings.config_table, bundle)?;
+        settings.bundle_settings =
+            settings_with_config(&settings.bundle_table, &settings.config_table)?;
+        Ok(settings)
+    }
+
+    /// Returns the name of the variant being bundled, if any.
+    pub fn variant(&self) -> Option<&str> {
+        self.variant.as_deref()
+    }
+
+    /// Returns the time recorded in the bundles (e.g. as the modification
+    /// time of the files in archives), in seconds since the Unix epoch: the
+    /// `SOURCE_DATE_EPOCH` environment variable if it is set, so that bundles
+    /// are reproducible, or else the time the settings were built.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Returns the name of the package being bundled.
+    pub fn package_name(&self) -> &str {
+        &self.package.name
+    }
+
     /// Returns the directory where the bundle should be placed.
     pub fn project_out_directory(&self) -> &Path {
         &self.project_out_directory
@@ -262,27 +647,47 @@ impl Settings {
         &self.binary_path
     }
 
-    /// If a specific package type was specified by the command-line, returns
-    /// that package type; otherwise, if a target triple was specified by the
-    /// command-line, returns the native package type(s) for that target;
-    /// otherwise, returns the native package type(s) for the host platform.
+    /// Returns true if the binary was given (with `--binary`) rather than
+    /// built by cargo-bundle.
+    pub fn has_prebuilt_binary(&self) -> bool {
+        self.prebuilt_binary
+    }
+
+    /// Returns true if `artifact`, from one of cargo's `compiler-artifact`
+    /// messages, is the binary being bundled.
+    pub fn is_built_artifact(&self, artifact: &cargo_metadata::Artifact) -> bool {
+        let kind = match self.build_artifact {
+            BuildArtifact::Example(_) => "example",
+            BuildArtifact::Main | BuildArtifact::Bin(_) => "bin",
+        };
+        artifact.package_id == self.package.id
+            && artifact.target.name == self.bin_target
+            && artifact.target.kind.iter().any(|k| k == kind)
+    }
+
+    /// Records the path of the binary being bundled, as reported by cargo.
+    /// Bundles are placed in a `bundle` directory next to the binary.
+    pub fn set_built_executable(&mut self, path: PathBuf) {
+        if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+            self.binary_name = installed_binary_name(name, self.variant.as_deref());
+        }
+        if let Some(dir) = path.parent() {
+            self.project_out_directory = dir.to_path_buf();
+        }
+        self.binary_path = path;
+    }
+
+    /// If specific package types were specified by the command-line, returns
+    /// those package types; otherwise, if a target triple was specified by the
+    /// command-line, returns the native package type for that target;
+    /// otherwise, returns the native package type for the host platform.
     /// Fails if the host/target's native package type is not supported.
     pub fn bundles(&self) -> Result<Vec<Bundle>, Error> {
-        if let Some(bundle) = self.bundle {
-            Ok(vec![bundle])
+        if !self.bundles.is_empty() {
+            Ok(self.bundles.clone())
         } else {
-            let target_os = if let Some((_, ref info)) = self.target {
-                info.target_os()
-            } else {
-                std::env::consts::OS
-            };
-
-            match target_os {
-                "macos" => Ok(vec![Bundle::MacosApplication]),
-                "linux" => Ok(vec![Bundle::LinuxDeb]),
-                "windows" => Ok(vec![Bundle::WindowsMsi]),
-                os => Err(Error::OSNotSupported(os.to_owned())),
-            }
+            let all = Bundle::all_for_os(target_os(&self.target))?;
+            Ok(all[..1].to_vec())
         }
     }
 
@@ -344,13 +749,22 @@ impl Settings {
     }
 
     /// Returns an iterator over the icon files to be used for this bundle.
-    pub fn icon_files(&self) -> ResourcePaths {
+    pub fn icon_files(&self) -> ResourcePaths<'_> {
         match self.bundle_settings.icon {
             Some(ref paths) => ResourcePaths::new(paths.as_slice(), false),
             None => ResourcePaths::new(&[], false),
         }
     }
 
+    /// Returns an iterator over the resource files to be included in this
+    /// bundle.  Directories are walked recursively.
+    pub fn resource_files(&self) -> ResourcePaths<'_> {
+        match self.bundle_settings.resources {
+            Some(ref paths) => ResourcePaths::new(paths.as_slice(), true),
+            None => ResourcePaths::new(&[], true),
+        }
+    }
+
     pub fn version_string(&self) -> &dyn Display {
         match self.bundle_settings.version.as_ref() {
             Some(v) => v,
@@ -358,6 +772,61 @@ impl Settings {
         }
     }
 
+    /// Returns the version of the `deb` package, e.g. `1:1.2.0~beta.1-1`.
+    pub fn deb_version_string(&self) -> String {
+        let upstream = match self.bundle_settings.deb_version {
+            Some(ref version) => version.clone(),
+            None => version::debian_upstream_version(&self.version_string().to_string()),
+        };
+        version::debian_version(
+            &upstream,
+            self.bundle_settings.deb_epoch,
+            self.bundle_settings.deb_revision.as_deref(),
+        )
+    }
+
+    /// Returns the version of the `rpm` package, e.g. `1.2.0~beta.1`.
+    pub fn rpm_version_string(&self) -> String {
+        version::rpm_version(&self.version_string().to_string())
+    }
+
+    /// Returns the release of the `rpm` package: the `rpm_release` setting,
+    /// or `1`.
+    pub fn rpm_release(&self) -> &str {
+        self.bundle_settings.rpm_release.as_deref().unwrap_or("1")
+    }
+
+    /// Returns the `ProductVersion` of the MSI installer, which must be
+    /// `MAJOR.MINOR.BUILD`, at most 255.255.65535.
+    pub fn msi_version_string(&self) -> Result<String, Error> {
+        match self.bundle_settings.msi_version {
+            Some(ref version) => version::msi_version(version),
+            None => version::msi_version(&self.version_string().to_string()),
+        }
+    }
+
+    /// Returns the `CFBundleShortVersionString` of Apple bundles: the
+    /// numeric `MAJOR.MINOR.PATCH` part of the version.
+    pub fn apple_short_version_string(&self) -> String {
+        version::apple_version(&self.version_string().to_string())
+    }
+
+    /// Returns the `CFBundleVersion` of the `.app` bundle.
+    pub fn osx_bundle_version(&self) -> String {
+        match self.bundle_settings.osx_bundle_version {
+            Some(ref version) => version.clone(),
+            None => self.apple_short_version_string(),
+        }
+    }
+
+    /// Returns the `CFBundleVersion` of the iOS `.app` bundle.
+    pub fn ios_bundle_version(&self) -> String {
+        match self.bundle_settings.ios_bundle_version {
+            Some(ref version) => version.clone(),
+            None => self.apple_short_version_string(),
+        }
+    }
+
     pub fn copyright_string(&self) -> Option<&str> {
         self.bundle_settings.copyright.as_deref()
     }
@@ -375,6 +844,10 @@ impl Settings {
         }
     }
 
+    pub fn license(&self) -> Option<&str> {
+        self.package.license.as_deref()
+    }
+
     pub fn homepage_url(&self) -> &str {
         self.package.homepage.as_deref().unwrap_or("")
     }
@@ -401,6 +874,118 @@ impl Settings {
         }
     }
 
+    /// Returns the relationship fields of the `deb` control file (e.g.
+    /// `Depends`), with the packages listed in each.
+    pub fn debian_relations(&self) -> [(&'static str, &[String]); 9] {
+        let settings = &self.bundle_settings;
+        fn list(packages: &Option<Vec<String>>) -> &[String] {
+            packages.as_deref().unwrap_or_default()
+        }
+        [
+            ("Pre-Depends", list(&settings.deb_pre_depends)),
+            ("Depends", self.debian_dependencies()),
+            ("Recommends", list(&settings.deb_recommends)),
+            ("Suggests", list(&settings.deb_suggests)),
+            ("Enhances", list(&settings.deb_enhances)),
+            ("Breaks", list(&settings.deb_breaks)),
+            ("Conflicts", list(&settings.deb_conflicts)),
+            ("Replaces", list(&settings.deb_replaces)),
+            ("Provides", list(&settings.deb_provides)),
+        ]
+    }
+
+    /// Returns the maintainer of the `deb` package: the `deb_maintainer`
+    /// setting, or the first of the package's authors.
+    pub fn deb_maintainer(&self) -> Option<&str> {
+        self.bundle_settings
+            .deb_maintainer
+            .as_deref()
+            .or_else(|| self.package.authors.first().map(String::as_str))
+    }
+
+    pub fn deb_section(&self) -> Option<&str> {
+        self.bundle_settings.deb_section.as_deref()
+    }
+
+    pub fn deb_priority(&self) -> Option<DebPriority> {
+        self.bundle_settings.deb_priority
+    }
+
+    pub fn deb_essential(&self) -> bool {
+        self.bundle_settings.deb_essential.unwrap_or(false)
+    }
+
+    /// Returns the other fields of the `deb` control file, in order of name.
+    pub fn deb_fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.bundle_settings
+            .deb_fields
+            .iter()
+            .flatten()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Returns the setting for the `deb` maintainer script named `script`
+    /// (`preinst`, `postinst`, `prerm` or `postrm`): the script itself, or
+    /// `file:<path>` to read it from a file.
+    pub fn deb_maintainer_script(&self, script: &str) -> Option<&str> {
+        let setting = match script {
+            "preinst" => &self.bundle_settings.deb_preinst,
+            "postinst" => &self.bundle_settings.deb_postinst,
+            "prerm" => &self.bundle_settings.deb_prerm,
+            "postrm" => &self.bundle_settings.deb_postrm,
+            _ => return None,
+        };
+        setting.as_deref()
+    }
+
+    pub fn deb_compression(&self) -> DebCompression {
+        self.bundle_settings.deb_compression.unwrap_or_default()
+    }
+
+    /// Returns the level to compress the `deb` package's archives at: the
+    /// `deb_compression_level` setting, or the default for the compression.
+    /// Fails if the level is out of range, or the compression has no levels.
+    pub fn deb_compression_level(&self) -> Result<Option<u32>, Error> {
+        let compression = self.deb_compression();
+        match (
+            self.bundle_settings.deb_compression_level,
+            compression.levels(),
+        ) {
+            (Some(level), Some((min, _, max))) if !(min..=max).contains(&level) => {
+                Err(Error::InvalidCompressionLevel(format!(
+                    "{level} is out of range for {} compression ({min} to {max})",
+                    compression.as_str()
+                )))
+            }
+            (Some(_), None) => Err(Error::InvalidCompressionLevel(format!(
+                "{} compression has no levels (only xz and zstd do)",
+                compression.as_str()
+            ))),
+            (level, levels) => Ok(levels.map(|(_, default, _)| level.unwrap_or(default))),
+        }
+    }
+
+    pub fn deb_shlibdeps(&self) -> bool {
+        self.bundle_settings.deb_shlibdeps.unwrap_or(false)
+    }
+
+    pub fn deb_dpkg_dir(&self) -> &Path {
+        Path::new(
+            self.bundle_settings
+                .deb_dpkg_dir
+                .as_deref()
+                .unwrap_or("/var/lib/dpkg"),
+        )
+    }
+
+    pub fn deb_shlibdeps_overrides(&self) -> Option<&BTreeMap<String, String>> {
+        self.bundle_settings.deb_shlibdeps_overrides.as_ref()
+    }
+
+    pub fn appimage_runtime(&self) -> Option<&str> {
+        self.bundle_settings.appimage_runtime.as_deref()
+    }
+
     pub fn linux_mime_types(&self) -> &[String] {
         match self.bundle_settings.linux_mime_types {
             Some(ref mime_types) => mime_types.as_slice(),
@@ -433,6 +1018,24 @@ impl Settings {
             None => &[],
         }
     }
+
+    pub fn ios_minimum_os_version(&self) -> Option<&str> {
+        self.bundle_settings.ios_minimum_os_version.as_deref()
+    }
+
+    pub fn ios_device_family(&self) -> &[IosDeviceFamily] {
+        match self.bundle_settings.ios_device_family {
+            Some(ref families) => families.as_slice(),
+            None => &[],
+        }
+    }
+
+    pub fn ios_supported_orientations(&self) -> &[IosOrientation] {
+        match self.bundle_settings.ios_supported_orientations {
+            Some(ref orientations) => orientations.as_slice(),
+            None => &[],
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -442,58 +1045,845 @@ pub enum BuildArtifact {
     Example(String),
 }
 
-#[derive(Clone, Debug, Default, Deserialize)]
+/// The priority of a Debian package.  See
+/// https://www.debian.org/doc/debian-policy/ch-archive.html#priorities
+#[derive(Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DebPriority {
+    Required,
+    Important,
+    Standard,
+    Optional,
+    Extra,
+}
+
+impl DebPriority {
+    /// Returns the `Priority` value for this priority.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DebPriority::Required => "required",
+            DebPriority::Important => "important",
+            DebPriority::Standard => "standard",
+            DebPriority::Optional => "optional",
+            DebPriority::Extra => "extra",
+        }
+    }
+}
+
+/// How the archives in a `deb` package are compressed.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DebCompression {
+    /// gzip, which every version of `dpkg` can unpack.
+    #[default]
+    Gzip,
+    /// xz, which `dpkg` can unpack since version 1.15.6.
+    Xz,
+    /// zstd, which `dpkg` can unpack since version 1.21.18.
+    Zstd,
+    /// No compression.
+    None,
+}
+
+impl DebCompression {
+    /// The names of the compressions, as used by `deb_compression`.
+    pub const ALL_NAMES: &'static [&'static str] = &["gzip", "xz", "zstd", "none"];
+
+    /// Returns the name of this compression, as used by `deb_compression`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DebCompression::Gzip => "gzip",
+            DebCompression::Xz => "xz",
+            DebCompression::Zstd => "zstd",
+            DebCompression::None => "none",
+        }
+    }
+
+    /// Returns the extension of archives compressed this way, after `.tar`.
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            DebCompression::Gzip => Some("gz"),
+            DebCompression::Xz => Some("xz"),
+            DebCompression::Zstd => Some("zst"),
+            DebCompression::None => None,
+        }
+    }
+
+    /// Fails if `cargo-bundle` was built without the cargo feature this
+    /// compression needs.  xz and zstd are compressed by C libraries, so they
+    /// are behind the optional `xz` and `zstd` features.
+    pub fn check_available(&self) -> Result<(), Error> {
+        match self {
+            DebCompression::Xz if !cfg!(feature = "xz") => {
+                Err(Error::CompressionFeatureMissing("xz"))
+            }
+            DebCompression::Zstd if !cfg!(feature = "zstd") => {
+                Err(Error::CompressionFeatureMissing("zstd"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the lowest, default and highest compression levels, for the
+    /// compressions that have levels.
+    pub fn levels(&self) -> Option<(u32, u32, u32)> {
+        match self {
+            DebCompression::Xz => Some((0, 6, 9)),
+            DebCompression::Zstd => Some((1, 3, 22)),
+            DebCompression::Gzip | DebCompression::None => None,
+        }
+    }
+}
+
+/// A kind of device that an iOS app can run on.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IosDeviceFamily {
+    Iphone,
+    Ipad,
+}
+
+impl IosDeviceFamily {
+    /// Returns the `UIDeviceFamily` value for this device family.
+    pub fn ui_device_family(&self) -> u32 {
+        match self {
+            IosDeviceFamily::Iphone => 1,
+            IosDeviceFamily::Ipad => 2,
+        }
+    }
+}
+
+/// An interface orientation supported by an iOS app.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum IosOrientation {
+    Portrait,
+    PortraitUpsideDown,
+    LandscapeLeft,
+    LandscapeRight,
+}
+
+impl IosOrientation {
+    /// Returns the `UISupportedInterfaceOrientations` value for this
+    /// orientation.
+    pub fn ui_orientation(&self) -> &'static str {
+        match self {
+            IosOrientation::Portrait => "UIInterfaceOrientationPortrait",
+            IosOrientation::PortraitUpsideDown => "UIInterfaceOrientationPortraitUpsideDown",
+            IosOrientation::LandscapeLeft => "UIInterfaceOrientationLandscapeLeft",
+            IosOrientation::LandscapeRight => "UIInterfaceOrientationLandscapeRight",
+        }
+    }
+}
+
+/// The settings in a `[package.metadata.bundle]` section.
+///
+/// The doc comments on the fields are the descriptions in the JSON Schema
+/// printed by `cargo bundle schema`.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[schemars(title = "[package.metadata.bundle]")]
 struct Metadata {
     // General settings:
+    /// The name of the application.  Defaults to the name of the package.
     name: Option<String>,
+    /// A unique reverse-DNS identifier for the application, e.g.
+    /// `"com.example.appname"`.
     identifier: Option<String>,
+    /// Paths (or glob patterns) of the application's icon files.
     icon: Option<Vec<String>>,
+    /// The version of the application.  Defaults to the package version.
     version: Option<String>,
+    /// Paths (or glob patterns) of files and directories to include in the
+    /// bundle.
+    resources: Option<Vec<String>>,
+    /// A copyright notice for the application.
     copyright: Option<String>,
+    /// The kind of application, used by the OS to categorize it.
     category: Option<Category>,
+    /// A short description of the application.  Defaults to the package
+    /// description.
     short_description: Option<String>,
+    /// A longer, multi-line description of the application.
     long_description: Option<String>,
     // OS-specific settings:
+    /// MIME types the application can open, for its `.desktop` file.
     linux_mime_types: Option<Vec<String>>,
+    /// Arguments passed to the binary by the `Exec` line of the `.desktop`
+    /// file, e.g. `"%f"`.
     linux_exec_args: Option<String>,
+    /// Whether the application runs in a terminal.
     linux_use_terminal: Option<bool>,
+    /// Debian packages the `.deb` package depends on.
     deb_depends: Option<Vec<String>>,
+    /// Debian packages that must be installed before the `.deb` package is
+    /// unpacked (its `Pre-Depends`).
+    deb_pre_depends: Option<Vec<String>>,
+    /// Debian packages that are installed along with the `.deb` package,
+    /// unless the user chooses otherwise (its `Recommends`).
+    deb_recommends: Option<Vec<String>>,
+    /// Debian packages that are useful along with the `.deb` package (its
+    /// `Suggests`).
+    deb_suggests: Option<Vec<String>>,
+    /// Debian packages that the `.deb` package is useful along with (its
+    /// `Enhances`).
+    deb_enhances: Option<Vec<String>>,
+    /// Debian packages that the `.deb` package breaks (its `Breaks`).
+    deb_breaks: Option<Vec<String>>,
+    /// Debian packages that can't be installed along with the `.deb` package
+    /// (its `Conflicts`).
+    deb_conflicts: Option<Vec<String>>,
+    /// Debian packages whose files the `.deb` package replaces (its
+    /// `Replaces`).
+    deb_replaces: Option<Vec<String>>,
+    /// Virtual Debian packages that the `.deb` package provides (its
+    /// `Provides`).
+    deb_provides: Option<Vec<String>>,
+    /// The maintainer of the `.deb` package, as `"Name <email>"`.  Defaults
+    /// to the first of the package's authors.
+    deb_maintainer: Option<String>,
+    /// The archive section of the `.deb` package, e.g. `"utils"`.
+    deb_section: Option<String>,
+    /// The priority of the `.deb` package.
+    deb_priority: Option<DebPriority>,
+    /// Whether the `.deb` package is essential, so that it can't be removed.
+    deb_essential: Option<bool>,
+    /// Other fields of the `.deb` package's control file, by name (e.g.
+    /// `X-Origin`); the names must start with `X-`.
+    deb_fields: Option<BTreeMap<String, String>>,
+    /// The upstream part of the `.deb` package's version.  Defaults to the
+    /// version, with a pre-release after a `~` (e.g. `"1.2.0~beta.1"`).
+    deb_version: Option<String>,
+    /// The epoch of the `.deb` package's version, for when the version
+    /// scheme has changed so that newer versions would sort as older.
+    deb_epoch: Option<u32>,
+    /// The Debian revision of the `.deb` package's version, e.g. `"1"`.
+    deb_revision: Option<String>,
+    /// The `.deb` package's `preinst` maintainer script: the script itself,
+    /// or `"file:<path>"` to read it from a file.
+    deb_preinst: Option<String>,
+    /// The `.deb` package's `postinst` maintainer script: the script itself,
+    /// or `"file:<path>"` to read it from a file.
+    deb_postinst: Option<String>,
+    /// The `.deb` package's `prerm` maintainer script: the script itself, or
+    /// `"file:<path>"` to read it from a file.
+    deb_prerm: Option<String>,
+    /// The `.deb` package's `postrm` maintainer script: the script itself,
+    /// or `"file:<path>"` to read it from a file.
+    deb_postrm: Option<String>,
+    /// How to compress the `.deb` package's archives: `"gzip"` (the
+    /// default), `"xz"`, `"zstd"` or `"none"`.
+    deb_compression: Option<DebCompression>,
+    /// The level to compress the `.deb` package's archives at, for xz (0 to
+    /// 9, default 6) or zstd (1 to 22, default 3).
+    deb_compression_level: Option<u32>,
+    /// Whether to add the packages containing the shared libraries that the
+    /// binary links to to the `.deb` package's `Depends`, from the dpkg
+    /// database.
+    deb_shlibdeps: Option<bool>,
+    /// The dpkg database to find the packages containing shared libraries in.
+    /// Defaults to `"/var/lib/dpkg"`.
+    deb_dpkg_dir: Option<String>,
+    /// The relations to depend on for shared libraries, by soname (e.g.
+    /// `"libssl.so.3" = "libssl3 (>= 3.0.2)"`), instead of the ones found in
+    /// the dpkg database.  An empty string adds no relation for the library.
+    deb_shlibdeps_overrides: Option<BTreeMap<String, String>>,
+    /// The release of the `.rpm` package, counting the builds of the same
+    /// version (e.g. `"2"` for a fixed rebuild).  Defaults to `"1"`.
+    rpm_release: Option<String>,
+    /// Path of the AppImage runtime to use for `.AppImage` bundles.
+    appimage_runtime: Option<String>,
+    /// The `ProductVersion` of the `.msi` installer.  Defaults to the numeric
+    /// `MAJOR.MINOR.PATCH` part of the version, which must be at most
+    /// 255.255.65535.
+    msi_version: Option<String>,
+    /// Names or paths of macOS frameworks to include in the `.app` bundle.
     osx_frameworks: Option<Vec<String>>,
+    /// The minimum macOS version the application runs on, e.g. `"10.11"`.
     osx_minimum_system_version: Option<String>,
+    /// The `CFBundleVersion` of the `.app` bundle.  Defaults to the numeric
+    /// `MAJOR.MINOR.PATCH` part of the version.
+    osx_bundle_version: Option<String>,
+    /// URL schemes the application handles.
     osx_url_schemes: Option<Vec<String>>,
-    // Bundles for other binaries/examples:
+    /// The minimum iOS version the application runs on, e.g. `"13.0"`.
+    ios_minimum_os_version: Option<String>,
+    /// The `CFBundleVersion` of the iOS `.app` bundle.  Defaults to the
+    /// numeric `MAJOR.MINOR.PATCH` part of the version.
+    ios_bundle_version: Option<String>,
+    /// The kinds of iOS device the application runs on.
+    ios_device_family: Option<Vec<IosDeviceFamily>>,
+    /// The interface orientations the application supports.
+    ios_supported_orientations: Option<Vec<IosOrientation>>,
+    // Bundles for other binaries/examples (and the list fields they don't
+    // inherit), variants, and overrides for particular targets and bundle
+    // formats.  These tables are merged from the raw JSON
+    // (see `SettingsBuilder::build`), but are declared here so that their
+    // contents are checked when the metadata is parsed (and their keys by
+    // `check_nested_tables`).
+    /// List settings which a `bin` or `example` table replaces, rather than
+    /// adding to those of the package.
+    no_inherit: Option<Vec<String>>,
+    /// Settings for bundles of other binaries, by binary name (see `--bin`).
     bin: Option<HashMap<String, Metadata>>,
+    /// Settings for bundles of examples, by example name (see `--example`).
     example: Option<HashMap<String, Metadata>>,
+    /// Settings for variants of the application, by variant name (see
+    /// `--variant`).
+    variant: Option<HashMap<String, Metadata>>,
+    /// Settings for particular targets, by target triple or `cfg(...)`
+    /// expression.
+    target: Option<HashMap<String, Metadata>>,
+    /// Settings for particular bundle formats, by format name (e.g. `deb`).
+    format: Option<HashMap<String, Metadata>>,
+}
+
+impl Metadata {
+    /// Checks what parsing the metadata can't, in these settings and every
+    /// table nested within them: that `no_inherit` only names list settings,
+    /// and that the keys of the `target` and `format` tables are platforms
+    /// and bundle formats.
+    fn check_nested_tables(&self) -> Result<(), Error> {
+        for field in self.no_inherit.iter().flatten() {
+            if !LIST_FIELDS.contains(&field.as_str()) {
+                return Err(Error::NotAListField(field.clone()));
+            }
+        }
+        for key in self.target.iter().flat_map(HashMap::keys) {
+            Platform::from_str(key)?;
+        }
+        for name in self.format.iter().flat_map(HashMap::keys) {
+            Bundle::from_short_name(name)?;
+        }
+        let nested = [
+            &self.bin,
+            &self.example,
+            &self.variant,
+            &self.target,
+            &self.format,
+        ];
+        for settings in nested.into_iter().flatten().flat_map(HashMap::values) {
+            settings.check_nested_tables()?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns a JSON Schema for the `[package.metadata.bundle]` section.
+pub fn metadata_schema() -> Value {
+    // TOML has no null, so optional settings are just left out.
+    let settings = schemars::gen::SchemaSettings::draft07().with(|settings| {
+        settings.option_add_null_type = false;
+    });
+    let schema = settings.into_generator().into_root_schema_for::<Metadata>();
+    serde_json::to_value(schema).unwrap()
+}
+
+/// Returns the time given by the `SOURCE_DATE_EPOCH` environment variable (see
+/// https://reproducible-builds.org/specs/source-date-epoch/), or the current
+/// time if it isn't set.
+fn source_date_epoch() -> Result<u64, Error> {
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => parse_source_date_epoch(&epoch),
+        Err(_) => Ok(std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0)),
+    }
+}
+
+/// Parses a `SOURCE_DATE_EPOCH` value.  Times that don't fit in 32 bits (after
+/// 2106) are rejected, as the rpm and squashfs formats can't store them.
+fn parse_source_date_epoch(epoch: &str) -> Result<u64, Error> {
+    epoch
+        .trim()
+        .parse::<u32>()
+        .map(u64::from)
+        .map_err(|_| Error::InvalidSourceDateEpoch(epoch.to_string()))
 }
 
 /// Try to load `Cargo.toml` file in the specified directory
-fn load_metadata(dir: &Path) -> Result<cargo_metadata::Metadata, Error> {
+pub(crate) fn load_metadata(dir: &Path) -> Result<cargo_metadata::Metadata, Error> {
     let cargo_file_path = dir.join("Cargo.toml");
     Ok(MetadataCommand::new()
         .manifest_path(cargo_file_path)
         .exec()?)
 }
 
-fn bundle_settings_from_table(
-    opt_map: &Option<HashMap<String, Metadata>>,
-    map_name: &str,
-    bundle_name: &str,
-) -> Result<Metadata, Error> {
-    if let Some(bundle_settings) = opt_map.as_ref().and_then(|map| map.get(bundle_name)) {
-        Ok(bundle_settings.clone())
-    } else {
-        terminal::print_warning(&format!(
-            "No [package.metadata.bundle.{}.{}] section in Cargo.toml",
-            map_name, bundle_name
-        ))?;
-        Ok(Metadata::default())
+/// Layers a package's `[package.metadata.bundle]` table over the workspace's
+/// `[workspace.metadata.bundle]` table.  As with cargo's own workspace
+/// inheritance, a package can also name a setting it takes from the
+/// workspace explicitly, with `<setting>.workspace = true`.
+fn inherit_workspace_table(workspace_table: &Value, package_table: Value) -> Result<Value, Error> {
+    let mut table = match workspace_table {
+        Value::Object(_) => workspace_table.clone(),
+        _ => json!({}),
+    };
+    if let Value::Object(package_table) = package_table {
+        for (key, value) in package_table {
+            if value == json!({ "workspace": true }) {
+                if workspace_table.get(&key).is_none() {
+                    return Err(Error::WorkspaceSettingNotFound(key));
+                }
+                continue;
+            }
+            merge_tables(&mut table[&key], &value);
+        }
     }
+    Ok(table)
 }
 
-pub struct ResourcePaths<'a> {
-    pattern_iter: std::slice::Iter<'a, String>,
-    glob_iter: Option<glob::Paths>,
-    walk_iter: Option<walkdir::IntoIter>,
+/// Merges each `[package.metadata.bundle.target.'cfg(...)']` table whose
+/// platform matches the target being bundled for over the bundle settings.
+fn apply_target_overrides(table: &mut Value, cfgs: &TargetCfgs) -> Result<(), Error> {
+    let target_overrides: Vec<(String, Value)> = match table.get("target") {
+        Some(Value::Object(overrides)) => overrides.clone().into_iter().collect(),
+        _ => Vec::new(),
+    };
+    for (key, overlay) in target_overrides {
+        let platform = Platform::from_str(&key)?;
+        if platform.matches(&cfgs.triple, &cfgs.cfgs) {
+            merge_tables(table, &overlay);
+        }
+    }
+    Ok(())
+}
+
+/// Merges each `[package.metadata.bundle.format.<format>]` table for the given
+/// kind of bundle over the bundle settings.
+fn apply_format_overrides(table: &mut Value, bundle: Bundle) -> Result<(), Error> {
+    let format_overrides: Vec<(String, Value)> = match table.get("format") {
+        Some(Value::Object(overrides)) => overrides.clone().into_iter().collect(),
+        _ => Vec::new(),
+    };
+    for (name, overlay) in format_overrides {
+        if Bundle::from_short_name(&name)? == bundle {
+            merge_tables(table, &overlay);
+        }
+    }
+    Ok(())
+}
+
+/// Merges the variant's table from the `--config` overrides (if any) over the
+/// rest of them.  Unlike `apply_variant`, this leaves the name and identifier
+/// alone: they have already been given the variant's identity, and an
+/// override replaces them outright.
+fn apply_config_variant(config_table: &mut Value, variant: &str) {
+    if let Some(overlay) = config_table
+        .get("variant")
+        .and_then(|variants| variants.get(variant))
+        .cloned()
+    {
+        merge_tables(config_table, &overlay);
+    }
+}
+
+/// Parses the bundle settings with the `--config` overrides merged over them.
+/// The overrides are merged once, as the last layer, after the `bin`, `target`
+/// and `variant` tables (and, in `Settings::for_bundle`, the `format` tables)
+/// have been applied to both, so that they always win.
+fn settings_with_config(bundle_table: &Value, config_table: &Value) -> Result<Metadata, Error> {
+    let mut table = bundle_table.clone();
+    merge_tables(&mut table, config_table);
+    let settings: Metadata = serde_json::from_value(table)?;
+    settings.check_nested_tables()?;
+    Ok(settings)
+}
+
+/// Merges the `[package.metadata.bundle.variant.<variant>]` table over the
+/// bundle settings.  So that variants can be installed alongside each other,
+/// a variant that doesn't set its own `name` or `identifier` gets the
+/// variant name appended to them, e.g. `Foo beta` and `com.example.foo.beta`.
+fn apply_variant(table: &mut Value, variant: &str, package_name: &str) -> Result<(), Error> {
+    let overlay = table
+        .get("variant")
+        .and_then(|variants| variants.get(variant))
+        .cloned()
+        .ok_or_else(|| Error::VariantNotFound(variant.to_string(), package_name.to_string()))?;
+    if let Value::Object(ref mut settings) = table {
+        if overlay.get("name").is_none() {
+            let name = settings
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or(package_name);
+            let name = format!("{name} {variant}");
+            settings.insert("name".to_string(), Value::String(name));
+        }
+        if overlay.get("identifier").is_none() {
+            if let Some(identifier) = settings.get("identifier").and_then(Value::as_str) {
+                let identifier = format!("{identifier}.{variant}");
+                settings.insert("identifier".to_string(), Value::String(identifier));
+            }
+        }
+    }
+    merge_tables(table, &overlay);
+    Ok(())
+}
+
+/// Returns the name the binary is installed under: its file name, with the
+/// variant name (if any) appended to the stem so that variants don't conflict,
+/// e.g. `foo-beta.exe`.
+fn installed_binary_name(file_name: &str, variant: Option<&str>) -> String {
+    match variant {
+        Some(variant) => match file_name.rsplit_once('.') {
+            Some((stem, extension)) => format!("{stem}-{variant}.{extension}"),
+            None => format!("{file_name}-{variant}"),
+        },
+        None => file_name.to_string(),
+    }
+}
+
+/// Parses a `--config` override of the form `<key>=<value>` into a table
+/// that can be merged over the bundle settings.
+fn parse_config(config: &str) -> Result<Value, Error> {
+    let invalid = |reason: String| Error::InvalidConfig(config.to_string(), reason);
+    let (key, value) = config
+        .split_once('=')
+        .ok_or_else(|| invalid("expected `<key>=<value>`".to_string()))?;
+    // Numbers are taken as strings (so that `version=1.2` works), unless the
+    // setting is a number, like `deb_epoch`.
+    let (value, number) = match toml::from_str::<toml::Value>(&format!("value = {value}")) {
+        Ok(toml::Value::Table(mut table)) => match table.remove("value") {
+            Some(
+                value @ (toml::Value::String(_)
+                | toml::Value::Boolean(_)
+                | toml::Value::Array(_)
+                | toml::Value::Table(_)),
+            ) => (serde_json::to_value(value)?, None),
+            Some(toml::Value::Integer(number)) => {
+                (Value::String(value.trim().to_string()), Some(number))
+            }
+            _ => (Value::String(value.trim().to_string()), None),
+        },
+        _ => (Value::String(value.trim().to_string()), None),
+    };
+
+    // Let TOML parse the key, so that quoted parts like
+    // `target.'cfg(unix)'.name` work as they do in `Cargo.toml`.
+    let mut table: Value = toml::from_str(&format!("{key} = 0"))
+        .map_err(|err| invalid(format!("invalid key: {err}")))?;
+    let leaf = config_leaf(&mut table);
+    *leaf = value;
+
+    if let Some((path, key)) = unknown_setting_key(&table, "package.metadata.bundle") {
+        let location = format!("`--config {config}`");
+        return Err(Error::UnknownSetting(
+            path,
+            location,
+            closest_setting_key(&key),
+        ));
+    }
+    if let Err(err) = serde_json::from_value::<Metadata>(table.clone()) {
+        let number = number.ok_or_else(|| invalid(err.to_string()))?;
+        *config_leaf(&mut table) = Value::from(number);
+        serde_json::from_value::<Metadata>(table.clone()).map_err(|_| invalid(err.to_string()))?;
+    }
+    Ok(table)
+}
+
+/// Returns the value of the single key in a table parsed by `parse_config`.
+fn config_leaf(table: &mut Value) -> &mut Value {
+    let mut leaf = table;
+    while let Value::Object(map) = leaf {
+        leaf = map.values_mut().next().unwrap();
+    }
+    leaf
+}
+
+/// The keys of the tables of settings nested within the bundle settings, e.g.
+/// `[package.metadata.bundle.bin.<name>]`.
+const NESTED_TABLE_KEYS: &[&str] = &["bin", "example", "variant", "target", "format"];
+
+/// Checks that every key in a table of bundle settings (and the tables nested
+/// within it) is a known setting.  Unknown keys are reported along with their
+/// location in `manifest_path`, and the closest known key if there is one.
+fn check_setting_keys(table: &Value, manifest_path: &Path, table_path: &str) -> Result<(), Error> {
+    match unknown_setting_key(table, table_path) {
+        Some((path, key)) => {
+            let mut key_path: Vec<String> = table_path.split('.').map(str::to_string).collect();
+            key_path.extend(unknown_setting_key_path(table).unwrap_or_default());
+            let location = match find_key_line(manifest_path, &key_path) {
+                Some(line) => format!("{}:{line}", manifest_path.display()),
+                None => manifest_path.display().to_string(),
+            };
+            Err(Error::UnknownSetting(
+                path,
+                location,
+                closest_setting_key(&key),
+            ))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Returns the first unknown key in a table of bundle settings, both as a
+/// dotted path starting with `table_path` and on its own.
+fn unknown_setting_key(table: &Value, table_path: &str) -> Option<(String, String)> {
+    let key_path = unknown_setting_key_path(table)?;
+    let mut path = table_path.to_string();
+    for key in &key_path {
+        path.push('.');
+        path.push_str(&toml_key(key));
+    }
+    Some((path, key_path.last()?.clone()))
+}
+
+/// Returns the path to the first unknown key in a table of bundle settings,
+/// relative to the table: the names of the nested tables it is in, then the
+/// key itself (e.g. `["bin", "foo", "short_desription"]`).
+fn unknown_setting_key_path(table: &Value) -> Option<Vec<String>> {
+    let table = match table {
+        Value::Object(table) => table,
+        _ => return None,
+    };
+    for (key, value) in table {
+        if NESTED_TABLE_KEYS.contains(&key.as_str()) {
+            if let Value::Object(nested) = value {
+                for (name, nested) in nested {
+                    if let Some(unknown) = unknown_setting_key_path(nested) {
+                        let mut path = vec![key.clone(), name.clone()];
+                        path.extend(unknown);
+                        return Some(path);
+                    }
+                }
+            }
+        } else if !SETTING_KEYS.contains(&key.as_str()) && key != "no_inherit" {
+            return Some(vec![key.clone()]);
+        }
+    }
+    None
+}
+
+/// Quotes a key for display in a dotted TOML path, if it needs quoting.
+fn toml_key(key: &str) -> Cow<'_, str> {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        key.into()
+    } else {
+        format!("'{key}'").into()
+    }
+}
+
+/// Returns the (1-based) number of the line of the manifest on which the key
+/// at `key_path` (e.g. `["package", "metadata", "bundle", "name"]`) is
+/// written, whether in a table header, a dotted key or an inline table.
+fn find_key_line(manifest_path: &Path, key_path: &[String]) -> Option<usize> {
+    let manifest = std::fs::read_to_string(manifest_path).ok()?;
+    key_line(&manifest, key_path)
+}
+
+/// Returns the (1-based) number of the line of the TOML `source` on which the
+/// key at `key_path` is written, using the spans `toml_edit` records.
+fn key_line(source: &str, key_path: &[String]) -> Option<usize> {
+    let document = toml_edit::ImDocument::parse(source).ok()?;
+    let (last, parents) = key_path.split_last()?;
+    let mut table: &dyn toml_edit::TableLike = document.as_table();
+    for key in parents {
+        table = table.get(key)?.as_table_like()?;
+    }
+    let span = table.key(last)?.span()?;
+    Some(source[..span.start].matches('\n').count() + 1)
+}
+
+/// Returns the known setting key most similar to the unknown `key`, if any
+/// is similar enough to be a likely typo.
+fn closest_setting_key(key: &str) -> Option<&'static str> {
+    const CONFIDENCE_THRESHOLD: f64 = 0.8;
+    let mut best_confidence = 0.0;
+    let mut best_key = None;
+    let keys = SETTING_KEYS
+        .iter()
+        .chain(NESTED_TABLE_KEYS)
+        .chain(&["no_inherit"]);
+    for &candidate in keys {
+        let confidence = strsim::jaro_winkler(key, candidate);
+        if confidence >= CONFIDENCE_THRESHOLD && confidence > best_confidence {
+            best_confidence = confidence;
+            best_key = Some(candidate);
+        }
+    }
+    best_key
+}
+
+/// The names of the top-level bundle settings, which can be set with
+/// `CARGO_BUNDLE_<KEY>` environment variables.
+const SETTING_KEYS: &[&str] = &[
+    "name",
+    "identifier",
+    "icon",
+    "version",
+    "resources",
+    "copyright",
+    "category",
+    "short_description",
+    "long_description",
+    "linux_mime_types",
+    "linux_exec_args",
+    "linux_use_terminal",
+    "deb_depends",
+    "deb_pre_depends",
+    "deb_recommends",
+    "deb_suggests",
+    "deb_enhances",
+    "deb_breaks",
+    "deb_conflicts",
+    "deb_replaces",
+    "deb_provides",
+    "deb_maintainer",
+    "deb_section",
+    "deb_priority",
+    "deb_essential",
+    "deb_fields",
+    "deb_version",
+    "deb_epoch",
+    "deb_revision",
+    "deb_preinst",
+    "deb_postinst",
+    "deb_prerm",
+    "deb_postrm",
+    "deb_compression",
+    "deb_compression_level",
+    "deb_shlibdeps",
+    "deb_dpkg_dir",
+    "deb_shlibdeps_overrides",
+    "rpm_release",
+    "appimage_runtime",
+    "msi_version",
+    "osx_frameworks",
+    "osx_minimum_system_version",
+    "osx_bundle_version",
+    "osx_url_schemes",
+    "ios_minimum_os_version",
+    "ios_bundle_version",
+    "ios_device_family",
+    "ios_supported_orientations",
+];
+
+/// The `Metadata` fields that hold lists, which can be named in `no_inherit`.
+const LIST_FIELDS: &[&str] = &[
+    "icon",
+    "resources",
+    "linux_mime_types",
+    "deb_depends",
+    "deb_pre_depends",
+    "deb_recommends",
+    "deb_suggests",
+    "deb_enhances",
+    "deb_breaks",
+    "deb_conflicts",
+    "deb_replaces",
+    "deb_provides",
+    "osx_frameworks",
+    "osx_url_schemes",
+    "ios_device_family",
+    "ios_supported_orientations",
+];
+
+/// Returns the settings for the binary or example `bundle_name`: the
+/// `[package.metadata.bundle.<map_name>.<bundle_name>]` table layered over the
+/// package-level settings.  Fields the table doesn't set are inherited, and
+/// lists are appended to the package-level lists, except for the list fields
+/// named in the table's `no_inherit` key.
+fn bundle_settings_from_table(
+    bundle_table: &Value,
+    map_name: &str,
+    bundle_name: &str,
+) -> Result<Value, Error> {
+    let mut settings = bundle_table.clone();
+    if let Value::Object(ref mut table) = settings {
+        table.remove("bin");
+        table.remove("example");
+    }
+    let overlay = match bundle_table
+        .get(map_name)
+        .and_then(|map| map.get(bundle_name))
+    {
+        Some(overlay) => overlay,
+        None => return Ok(settings),
+    };
+    let no_inherit: Vec<String> = match overlay.get("no_inherit") {
+        Some(value) => serde_json::from_value(value.clone())?,
+        None => Vec::new(),
+    };
+    for field in &no_inherit {
+        if !LIST_FIELDS.contains(&field.as_str()) {
+            return Err(Error::NotAListField(field.clone()));
+        }
+        if let Value::Object(ref mut table) = settings {
+            table.remove(field);
+        }
+    }
+    if let (Value::Object(settings), Value::Object(overlay)) = (&mut settings, overlay) {
+        for (key, value) in overlay {
+            match (settings.get_mut(key), value) {
+                (Some(Value::Array(base)), Value::Array(items)) => {
+                    for item in items {
+                        if !base.contains(item) {
+                            base.push(item.clone());
+                        }
+                    }
+                }
+                (Some(base), value) => merge_tables(base, value),
+                (None, value) => {
+                    settings.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+    Ok(settings)
+}
+
+/// Merges `overlay` into `base`: tables are merged key by key, recursively,
+/// and any other value in `overlay` replaces the one in `base`.
+fn merge_tables(base: &mut Value, overlay: &Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                merge_tables(base.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
+
+/// The target triple and `cfg` values (as printed by `rustc --print cfg`) of
+/// the target being bundled for, used to select `target` overrides.
+struct TargetCfgs {
+    triple: String,
+    cfgs: Vec<Cfg>,
+}
+
+impl TargetCfgs {
+    fn new(target: &Option<(String, TargetInfo)>) -> Result<TargetCfgs, Error> {
+        let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| OsString::from("rustc"));
+        let mut command = std::process::Command::new(rustc);
+        command.arg("--print=cfg");
+        let triple = match target {
+            Some((triple, _)) => {
+                command.arg(format!("--target={triple}"));
+                triple.clone()
+            }
+            None => host_triple().unwrap_or_default(),
+        };
+        let output = command.output()?;
+        if !output.status.success() {
+            return Err(Error::RustcCfgFailed(output.status));
+        }
+        let cfgs = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(Cfg::from_str)
+            .collect::<Result<_, _>>()?;
+        Ok(TargetCfgs { triple, cfgs })
+    }
+}
+
+pub struct ResourcePaths<'a> {
+    pattern_iter: std::slice::Iter<'a, String>,
+    glob_iter: Option<glob::Paths>,
+    walk_iter: Option<walkdir::IntoIter>,
     allow_walk: bool,
 }
 
@@ -535,7 +1925,8 @@ impl<'a> Iterator for ResourcePaths<'a> {
                     };
                     if path.is_dir() {
                         if self.allow_walk {
-                            let walk = walkdir::WalkDir::new(path);
+                            let walk = walkdir::WalkDir::new(path)
+                                .sort_by(|a, b| a.file_name().cmp(b.file_name()));
                             self.walk_iter = Some(walk.into_iter());
                             continue;
                         } else {
@@ -559,23 +1950,361 @@ impl<'a> Iterator for ResourcePaths<'a> {
     }
 }
 
-pub fn run(settings: Settings) -> Result<Vec<PathBuf>, Error> {
-    let mut paths = Vec::new();
+/// Returns the OS of the target triple, or of the host if no target was
+/// given.
+fn target_os(target: &Option<(String, TargetInfo)>) -> &str {
+    match target {
+        Some((_, info)) => info.target_os(),
+        None => std::env::consts::OS,
+    }
+}
 
-    for bundle in settings.bundles()? {
-        paths.append(&mut match bundle {
-            Bundle::MacosApplication => osx_bundle::bundle_project(&settings)?,
-            Bundle::WindowsMsi => msi_bundle::bundle_project(&settings)?,
-            Bundle::LinuxDeb => deb_bundle::bundle_project(&settings)?,
-        });
+/// Returns the host's target triple, as reported by `rustc -vV`.
+fn host_triple() -> Option<String> {
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| OsString::from("rustc"));
+    let output = std::process::Command::new(rustc).arg("-vV").output().ok()?;
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(str::to_string)
+}
+
+/// Picks the binary to bundle for the given target triple from the values of
+/// `--binary`, each of which is either a path or `<triple>=<path>`.  A path
+/// given for the exact triple wins over one given without a triple.
+fn select_binary<S: AsRef<str>>(binaries: &[S], triple: Option<&str>) -> Option<PathBuf> {
+    let mut fallback = None;
+    for binary in binaries {
+        let binary = binary.as_ref();
+        match binary.split_once('=') {
+            Some((binary_triple, path)) if !binary_triple.contains(['/', '\\']) => {
+                if Some(binary_triple) == triple {
+                    return Some(PathBuf::from(path));
+                }
+            }
+            _ => fallback = Some(PathBuf::from(binary)),
+        }
+    }
+    fallback
+}
+
+/// A bundle produced by a `Bundler`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Artifact {
+    /// The name of the package that was bundled.
+    pub package: String,
+    /// The format of the bundle.
+    pub format: Bundle,
+    /// The path to the bundle, which is a directory for some formats (such as
+    /// `.app` bundles).
+    pub path: PathBuf,
+    /// The size of the bundle in bytes; for a directory, the total size of its
+    /// contents.
+    pub size: u64,
+    /// The hex SHA-256 digest of the bundle, if it is a file.
+    pub sha256: Option<String>,
+    /// The target triple that the bundled binary was built for.
+    pub target: Option<String>,
+    /// The version of the bundled application.
+    pub version: String,
+}
+
+impl Artifact {
+    fn new(
+        settings: &Settings,
+        bundle: Bundle,
+        path: PathBuf,
+        target: &Option<String>,
+    ) -> Result<Artifact, Error> {
+        let (size, sha256) = if path.is_dir() {
+            (linux::total_dir_size(&path)?, None)
+        } else {
+            (
+                path.metadata()?.len(),
+                Some(file::generate_sha256sum(&path)?),
+            )
+        };
+        Ok(Artifact {
+            package: settings.package_name().to_string(),
+            format: bundle,
+            path,
+            size,
+            sha256,
+            target: target.clone(),
+            version: settings.version_string().to_string(),
+        })
     }
+}
+
+/// A bundle that a `Bundler` failed to create.
+#[derive(Debug)]
+pub struct BundleFailure {
+    /// The name of the package that was being bundled.
+    pub package: String,
+    /// The format of the bundle.
+    pub format: Bundle,
+    /// The target triple that the bundled binary was built for.
+    pub target: Option<String>,
+    /// Why the bundle couldn't be created.
+    pub error: Error,
+}
+
+/// The outcome of `Bundler::run`: the bundles that were created, and the ones
+/// that couldn't be.
+#[derive(Debug, Default)]
+pub struct BundleReport {
+    pub artifacts: Vec<Artifact>,
+    pub failures: Vec<BundleFailure>,
+}
 
-    Ok(paths)
+/// Progress reported to the callback given to `Bundler::run_with`.
+#[derive(Clone, Copy, Debug)]
+pub enum BundleEvent<'a> {
+    /// Building the binaries of the given packages with `cargo build` has
+    /// begun.
+    BuildStarted {
+        packages: &'a [&'a str],
+        target: Option<&'a str>,
+        profile: &'a str,
+    },
+    /// Creating a bundle of the given format for a package has begun.
+    Started {
+        package: &'a str,
+        format: Bundle,
+        target: Option<&'a str>,
+    },
+    /// A bundle was created.
+    Created(&'a Artifact),
+    /// Creating a bundle failed.
+    Failed(&'a BundleFailure),
+}
+
+/// Builds the binaries for a set of packages (unless told not to), then
+/// creates every requested bundle for each of them.
+///
+/// ```no_run
+/// use cargo_bundle::{Bundler, SettingsBuilder};
+///
+/// let settings = SettingsBuilder::new(".").workspace().build()?;
+/// let report = Bundler::new(settings).run()?;
+/// for artifact in &report.artifacts {
+///     println!("{} ({} bytes)", artifact.path.display(), artifact.size);
+/// }
+/// for failure in &report.failures {
+///     eprintln!("{}", failure.error);
+/// }
+/// # Ok::<(), cargo_bundle::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Bundler {
+    settings: Vec<Settings>,
+    build: bool,
+}
+
+impl Bundler {
+    pub fn new(settings: Vec<Settings>) -> Bundler {
+        Bundler {
+            settings,
+            build: true,
+        }
+    }
+
+    /// Bundles the existing binaries without running `cargo build`.
+    pub fn no_build(mut self) -> Self {
+        self.build = false;
+        self
+    }
+
+    /// Creates the bundles, returning a description of each one.  A failure
+    /// to create one bundle does not stop the others from being created; it
+    /// is returned in the report's `failures` instead.  An error is only
+    /// returned if bundling couldn't be attempted at all (e.g. the build
+    /// failed).
+    pub fn run(self) -> Result<BundleReport, Error> {
+        self.run_with(|_| Ok(()))
+    }
+
+    /// Like `run`, but calls `on_event` as each bundle is started and then
+    /// created or failed, so that progress can be reported as it happens.
+    pub fn run_with<F>(mut self, mut on_event: F) -> Result<BundleReport, Error>
+    where
+        F: FnMut(BundleEvent) -> Result<(), Error>,
+    {
+        if self.build && !self.settings.iter().any(Settings::has_prebuilt_binary) {
+            build::build_project_if_unbuilt(&mut self.settings, &mut on_event)?;
+        }
+
+        let mut report = BundleReport::default();
+        let host = host_triple();
+
+        for settings in &self.settings {
+            let target = settings
+                .target_triple()
+                .map(str::to_string)
+                .or(host.clone());
+            for bundle in settings.bundles()? {
+                on_event(BundleEvent::Started {
+                    package: settings.package_name(),
+                    format: bundle,
+                    target: target.as_deref(),
+                })?;
+                let result = settings.for_bundle(bundle).and_then(|settings| {
+                    let paths = match bundle {
+                        Bundle::MacosApplication => osx_bundle::bundle_project(&settings),
+                        Bundle::IosApplication => ios_bundle::bundle_project(&settings),
+                        Bundle::WindowsMsi => msi_bundle::bundle_project(&settings),
+                        Bundle::LinuxDeb => deb_bundle::bundle_project(&settings),
+                        Bundle::LinuxRpm => rpm_bundle::bundle_project(&settings),
+                        Bundle::LinuxAppImage => appimage_bundle::bundle_project(&settings),
+                    }?;
+                    paths
+                        .into_iter()
+                        .map(|path| Artifact::new(&settings, bundle, path, &target))
+                        .collect::<Result<Vec<_>, _>>()
+                });
+                match result {
+                    Ok(artifacts) => {
+                        for artifact in artifacts {
+                            on_event(BundleEvent::Created(&artifact))?;
+                            report.artifacts.push(artifact);
+                        }
+                    }
+                    Err(error) => {
+                        let package = settings.package_name().to_string();
+                        let failure = BundleFailure {
+                            error: Error::BundleFailed(
+                                bundle.short_name(),
+                                package.clone(),
+                                Box::new(error),
+                            ),
+                            package,
+                            format: bundle,
+                            target: target.clone(),
+                        };
+                        on_event(BundleEvent::Failed(&failure))?;
+                        report.failures.push(failure);
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Category, Metadata};
+    use super::{
+        apply_config_variant, apply_format_overrides, apply_target_overrides, apply_variant,
+        bundle_settings_from_table, closest_setting_key, inherit_workspace_table,
+        installed_binary_name, key_line, merge_tables, metadata_schema, parse_config,
+        parse_source_date_epoch, select_binary, settings_with_config, unknown_setting_key,
+        unknown_setting_key_path, Bundle, Category, IosDeviceFamily, IosOrientation, Metadata,
+        SettingsBuilder, TargetCfgs, NESTED_TABLE_KEYS, SETTING_KEYS,
+    };
+    use cargo_platform::Cfg;
+    use serde_json::{json, Value};
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    #[test]
+    fn bundle_short_names() {
+        for &bundle in Bundle::ALL {
+            assert_eq!(
+                Bundle::from_short_name(bundle.short_name()).unwrap(),
+                bundle
+            );
+        }
+        assert_eq!(
+            Bundle::from_short_name("app").unwrap(),
+            Bundle::MacosApplication
+        );
+        assert!(Bundle::from_short_name(Bundle::ALL_FOR_TARGET).is_err());
+        assert_eq!(
+            Bundle::all_for_os("linux").unwrap(),
+            &[Bundle::LinuxDeb, Bundle::LinuxRpm, Bundle::LinuxAppImage]
+        );
+        assert!(Bundle::all_for_os("plan9").is_err());
+    }
+
+    #[test]
+    fn settings_builder_rejects_debug_profile() {
+        let result = SettingsBuilder::new(".").profile("debug").build();
+        assert!(matches!(result, Err(crate::Error::DebugProfileIsReserved)));
+    }
+
+    #[test]
+    fn parse_source_date_epochs() {
+        assert_eq!(
+            parse_source_date_epoch("1700000000").unwrap(),
+            1_700_000_000
+        );
+        assert_eq!(
+            parse_source_date_epoch(
+                " 0
+"
+            )
+            .unwrap(),
+            0
+        );
+        assert_eq!(
+            parse_source_date_epoch("4294967295").unwrap(),
+            4_294_967_295
+        );
+        assert!(parse_source_date_epoch("4294967296").is_err());
+        assert!(parse_source_date_epoch("18446744073709551615").is_err());
+        assert!(parse_source_date_epoch("-1").is_err());
+        assert!(parse_source_date_epoch("yesterday").is_err());
+    }
+
+    #[test]
+    fn merge_override_tables() {
+        let mut base = json!({
+            "name": "Example",
+            "deb_depends": ["a", "b"],
+            "bin": { "foo": { "name": "Foo", "icon": ["foo.png"] } },
+        });
+        merge_tables(
+            &mut base,
+            &json!({
+                "deb_depends": ["c"],
+                "bin": { "foo": { "name": "Foo Linux" } },
+            }),
+        );
+        assert_eq!(
+            base,
+            json!({
+                "name": "Example",
+                "deb_depends": ["c"],
+                "bin": { "foo": { "name": "Foo Linux", "icon": ["foo.png"] } },
+            })
+        );
+    }
+
+    #[test]
+    fn select_binary_for_target() {
+        let binaries = [
+            "x86_64-unknown-linux-gnu=out/linux/app",
+            "out/app",
+            "aarch64-apple-darwin=out/mac/app",
+        ];
+        assert_eq!(
+            select_binary(&binaries, Some("aarch64-apple-darwin")),
+            Some(PathBuf::from("out/mac/app"))
+        );
+        assert_eq!(
+            select_binary(&binaries, Some("x86_64-pc-windows-msvc")),
+            Some(PathBuf::from("out/app"))
+        );
+        assert_eq!(
+            select_binary(&["dist/a=b/app"], None),
+            Some(PathBuf::from("dist/a=b/app"))
+        );
+        assert_eq!(
+            select_binary(&binaries[..1], Some("i686-linux-android")),
+            None
+        );
+    }
 
     #[test]
     fn parse_cargo_toml() {
@@ -593,6 +2322,10 @@ mod tests {
         assert_eq!(bundle.identifier, Some("com.example.app".to_string()));
         assert_eq!(bundle.icon, None);
         assert_eq!(bundle.version, None);
+        assert_eq!(
+            bundle.resources,
+            Some(vec!["data".to_string(), "foo/bar".to_string()])
+        );
         assert_eq!(bundle.category, Some(Category::PuzzleGame));
         assert_eq!(
             bundle.long_description,
@@ -604,6 +2337,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_ios_settings() {
+        let toml_str = "\
+            ios_minimum_os_version = \"12.0\"\n\
+            ios_device_family = [\"iphone\", \"ipad\"]\n\
+            ios_supported_orientations = [\"portrait\", \"landscape-left\"]\n";
+        let bundle: Metadata = toml::from_str(toml_str).unwrap();
+        assert_eq!(bundle.ios_minimum_os_version, Some("12.0".to_string()));
+        assert_eq!(
+            bundle.ios_device_family,
+            Some(vec![IosDeviceFamily::Iphone, IosDeviceFamily::Ipad])
+        );
+        assert_eq!(
+            bundle.ios_supported_orientations,
+            Some(vec![
+                IosOrientation::Portrait,
+                IosOrientation::LandscapeLeft
+            ])
+        );
+
+        let toml_str = "ios_device_family = [\"watch\"]\n";
+        assert!(toml::from_str::<Metadata>(toml_str).is_err());
+    }
+
     #[test]
     fn parse_bin_and_example_bundles() {
         let toml_str = "\
@@ -631,4 +2388,318 @@ mod tests {
         let baz: &Metadata = examples.get("baz").unwrap();
         assert_eq!(baz.name, Some("Baz Example".to_string()));
     }
+
+    #[test]
+    fn bin_bundles_inherit_package_settings() {
+        let toml_str = "\
+            name = \"Example\"\n\
+            identifier = \"com.example.app\"\n\
+            icon = [\"icon.png\"]\n\
+            deb_depends = [\"libc6\"]\n\
+            \n\
+            [bin.foo]\n\
+            name = \"Foo App\"\n\
+            icon = [\"foo.png\"]\n\
+            deb_depends = [\"libfoo\"]\n\
+            no_inherit = [\"icon\"]\n";
+        let table: Value = toml::from_str(toml_str).unwrap();
+
+        let foo = bundle_settings_from_table(&table, "bin", "foo").unwrap();
+        let foo: Metadata = serde_json::from_value(foo).unwrap();
+        assert_eq!(foo.name.as_deref(), Some("Foo App"));
+        assert_eq!(foo.identifier.as_deref(), Some("com.example.app"));
+        assert_eq!(foo.icon, Some(vec!["foo.png".to_string()]));
+        assert_eq!(
+            foo.deb_depends,
+            Some(vec!["libc6".to_string(), "libfoo".to_string()])
+        );
+        assert!(foo.bin.is_none());
+
+        let bar = bundle_settings_from_table(&table, "bin", "bar").unwrap();
+        let bar: Metadata = serde_json::from_value(bar).unwrap();
+        assert_eq!(bar.name.as_deref(), Some("Example"));
+        assert_eq!(bar.icon, Some(vec!["icon.png".to_string()]));
+
+        let bad: Value = toml::from_str("[bin.foo]\nno_inherit = [\"name\"]\n").unwrap();
+        assert!(bundle_settings_from_table(&bad, "bin", "foo").is_err());
+    }
+
+    #[test]
+    fn packages_inherit_workspace_settings() {
+        let workspace: Value = toml::from_str(
+            "copyright = \"Copyright (c) Jane Doe 2024\"\n\
+             category = \"Developer Tool\"\n\
+             deb_depends = [\"libc6\"]\n",
+        )
+        .unwrap();
+        let package: Value = toml::from_str(
+            "name = \"Example\"\n\
+             category = \"Utility\"\n\
+             copyright.workspace = true\n",
+        )
+        .unwrap();
+        let table = inherit_workspace_table(&workspace, package).unwrap();
+        let settings: Metadata = serde_json::from_value(table).unwrap();
+        assert_eq!(settings.name.as_deref(), Some("Example"));
+        assert_eq!(
+            settings.copyright.as_deref(),
+            Some("Copyright (c) Jane Doe 2024")
+        );
+        assert_eq!(settings.category, Some(Category::Utility));
+        assert_eq!(settings.deb_depends, Some(vec!["libc6".to_string()]));
+
+        let package: Value = toml::from_str("version.workspace = true\n").unwrap();
+        assert!(inherit_workspace_table(&workspace, package).is_err());
+    }
+
+    #[test]
+    fn parse_config_overrides() {
+        assert_eq!(
+            parse_config("version=1.2").unwrap(),
+            json!({ "version": "1.2" })
+        );
+        assert_eq!(
+            parse_config("bin.foo.name=Foo Nightly").unwrap(),
+            json!({ "bin": { "foo": { "name": "Foo Nightly" } } })
+        );
+        assert_eq!(
+            parse_config("deb_epoch=1").unwrap(),
+            json!({ "deb_epoch": 1 })
+        );
+        assert_eq!(
+            parse_config("deb_depends=[\"libc6\"]").unwrap(),
+            json!({ "deb_depends": ["libc6"] })
+        );
+        assert_eq!(
+            parse_config("target.'cfg(unix)'.linux_use_terminal=true").unwrap(),
+            json!({ "target": { "cfg(unix)": { "linux_use_terminal": true } } })
+        );
+        assert!(parse_config("version").is_err());
+        assert!(parse_config("linux_use_terminal=maybe").is_err());
+        assert!(parse_config("category=Not A Category").is_err());
+    }
+
+    #[test]
+    fn variants_get_their_own_identity() {
+        let toml_str = "\
+            name = \"Foo\"\n\
+            identifier = \"com.example.foo\"\n\
+            icon = [\"foo.png\"]\n\
+            \n\
+            [variant.beta]\n\
+            icon = [\"foo-beta.png\"]\n\
+            \n\
+            [variant.nightly]\n\
+            name = \"Foo Nightly\"\n\
+            identifier = \"com.example.foo-nightly\"\n";
+        let table: Value = toml::from_str(toml_str).unwrap();
+
+        let mut beta = table.clone();
+        apply_variant(&mut beta, "beta", "foo").unwrap();
+        let beta: Metadata = serde_json::from_value(beta).unwrap();
+        assert_eq!(beta.name.as_deref(), Some("Foo beta"));
+        assert_eq!(beta.identifier.as_deref(), Some("com.example.foo.beta"));
+        assert_eq!(beta.icon, Some(vec!["foo-beta.png".to_string()]));
+
+        let mut nightly = table.clone();
+        apply_variant(&mut nightly, "nightly", "foo").unwrap();
+        let nightly: Metadata = serde_json::from_value(nightly).unwrap();
+        assert_eq!(nightly.name.as_deref(), Some("Foo Nightly"));
+        assert_eq!(
+            nightly.identifier.as_deref(),
+            Some("com.example.foo-nightly")
+        );
+
+        assert!(apply_variant(&mut table.clone(), "stable", "foo").is_err());
+        assert_eq!(
+            installed_binary_name("foo.exe", Some("beta")),
+            "foo-beta.exe"
+        );
+        assert_eq!(installed_binary_name("foo", None), "foo");
+    }
+
+    #[test]
+    fn variants_apply_over_target_overrides() {
+        let toml_str = "\
+            name = \"Foo\"\n\
+            icon = [\"foo.png\"]\n\
+            deb_depends = [\"libc6\"]\n\
+            \n\
+            [target.'cfg(unix)']\n\
+            icon = [\"foo-unix.png\"]\n\
+            deb_depends = [\"libgtk-3-0\"]\n\
+            \n\
+            [target.'cfg(windows)']\n\
+            icon = [\"foo.ico\"]\n\
+            \n\
+            [variant.beta]\n\
+            icon = [\"foo-beta.png\"]\n";
+        let mut table: Value = toml::from_str(toml_str).unwrap();
+        let cfgs = TargetCfgs {
+            triple: "x86_64-unknown-linux-gnu".to_string(),
+            cfgs: vec![Cfg::from_str("unix").unwrap()],
+        };
+        apply_target_overrides(&mut table, &cfgs).unwrap();
+        apply_variant(&mut table, "beta", "foo").unwrap();
+        let settings: Metadata = serde_json::from_value(table).unwrap();
+        assert_eq!(settings.icon, Some(vec!["foo-beta.png".to_string()]));
+        assert_eq!(settings.deb_depends, Some(vec!["libgtk-3-0".to_string()]));
+    }
+
+    #[test]
+    fn config_overrides_are_the_last_layer() {
+        let toml_str = "\
+            name = \"Foo\"\n\
+            copyright = \"Example\"\n\
+            \n\
+            [bin.foo]\n\
+            name = \"Foo App\"\n\
+            \n\
+            [target.'cfg(unix)']\n\
+            name = \"Foo for Unix\"\n\
+            \n\
+            [variant.beta]\n\
+            copyright = \"Example Beta\"\n\
+            \n\
+            [format.deb]\n\
+            name = \"foo-deb\"\n";
+        let table: Value = toml::from_str(toml_str).unwrap();
+        let mut config = json!({});
+        merge_tables(
+            &mut config,
+            &parse_config("bin.foo.name=Foo Nightly").unwrap(),
+        );
+        merge_tables(
+            &mut config,
+            &parse_config("variant.beta.copyright=Example Nightly").unwrap(),
+        );
+        let cfgs = TargetCfgs {
+            triple: "x86_64-unknown-linux-gnu".to_string(),
+            cfgs: vec![Cfg::from_str("unix").unwrap()],
+        };
+
+        let mut table = bundle_settings_from_table(&table, "bin", "foo").unwrap();
+        let mut config = bundle_settings_from_table(&config, "bin", "foo").unwrap();
+        apply_target_overrides(&mut table, &cfgs).unwrap();
+        apply_target_overrides(&mut config, &cfgs).unwrap();
+        apply_variant(&mut table, "beta", "foo").unwrap();
+        apply_config_variant(&mut config, "beta");
+        let settings = settings_with_config(&table, &config).unwrap();
+        assert_eq!(settings.name.as_deref(), Some("Foo Nightly"));
+        assert_eq!(settings.copyright.as_deref(), Some("Example Nightly"));
+
+        apply_format_overrides(&mut table, Bundle::LinuxDeb).unwrap();
+        apply_format_overrides(&mut config, Bundle::LinuxDeb).unwrap();
+        let settings = settings_with_config(&table, &config).unwrap();
+        assert_eq!(settings.name.as_deref(), Some("Foo Nightly"));
+    }
+
+    #[test]
+    fn check_nested_table_keys() {
+        let check = |toml_str: &str| {
+            toml::from_str::<Metadata>(toml_str)
+                .unwrap()
+                .check_nested_tables()
+        };
+        assert!(check("[bin.foo]\nno_inherit = [\"deb_depends\"]\n").is_ok());
+        assert!(check("[bin.foo]\nno_inherit = [\"name\"]\n").is_err());
+        assert!(check("[variant.beta.format.debian]\nname = \"Foo\"\n").is_err());
+        assert!(check("[target.'cfg(unix'.format.deb]\nname = \"Foo\"\n").is_err());
+        assert!(check("[target.'cfg(unix)'.format.deb]\nname = \"Foo\"\n").is_ok());
+    }
+
+    #[test]
+    fn reject_unknown_setting_keys() {
+        let table: Value = toml::from_str(
+            "name = \"Example\"\n\
+             \n\
+             [bin.foo]\n\
+             short_desription = \"A typo\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            unknown_setting_key(&table, "package.metadata.bundle"),
+            Some((
+                "package.metadata.bundle.bin.foo.short_desription".to_string(),
+                "short_desription".to_string()
+            ))
+        );
+        assert_eq!(
+            closest_setting_key("short_desription"),
+            Some("short_description")
+        );
+        assert_eq!(closest_setting_key("deb_depend"), Some("deb_depends"));
+        assert_eq!(closest_setting_key("xyzzy"), None);
+
+        let table: Value = toml::from_str(
+            "deb_depends = []\n\
+             [target.'cfg(unix)'.format.deb]\n\
+             no_inherit = [\"icon\"]\n",
+        )
+        .unwrap();
+        assert_eq!(unknown_setting_key(&table, "package.metadata.bundle"), None);
+        assert!(toml::from_str::<Metadata>("deb_depend = []").is_err());
+    }
+
+    #[test]
+    fn find_unknown_key_lines() {
+        let manifest = "\
+            [package]\n\
+            name = \"example\"\n\
+            description = \"An example\"\n\
+            \n\
+            [package.metadata.bundle]\n\
+            description = \"Misplaced\"\n\
+            bin.foo.homepage = \"https://example.com\"\n\
+            \n\
+            [package.metadata.bundle.target.'cfg(unix)']\n\
+            format = { deb = { description = \"Misplaced\" } }\n";
+        let key_path = |keys: &[&str]| -> Vec<String> {
+            ["package", "metadata", "bundle"]
+                .iter()
+                .chain(keys)
+                .map(|key| key.to_string())
+                .collect()
+        };
+        assert_eq!(key_line(manifest, &key_path(&["description"])), Some(6));
+        assert_eq!(
+            key_line(manifest, &key_path(&["bin", "foo", "homepage"])),
+            Some(7)
+        );
+        assert_eq!(
+            key_line(
+                manifest,
+                &key_path(&["target", "cfg(unix)", "format", "deb", "description"])
+            ),
+            Some(10)
+        );
+        assert_eq!(key_line(manifest, &key_path(&["homepage"])), None);
+
+        let table: Value = toml::from_str("description = \"Misplaced\"\n").unwrap();
+        assert_eq!(
+            unknown_setting_key_path(&table),
+            Some(vec!["description".to_string()])
+        );
+    }
+
+    #[test]
+    fn metadata_schema_lists_every_setting() {
+        let schema = metadata_schema();
+        let properties = schema["properties"].as_object().unwrap();
+        let mut keys: Vec<&str> = SETTING_KEYS.to_vec();
+        keys.extend(NESTED_TABLE_KEYS);
+        keys.push("no_inherit");
+        keys.sort_unstable();
+        let mut schema_keys: Vec<&str> = properties.keys().map(String::as_str).collect();
+        schema_keys.sort_unstable();
+        assert_eq!(schema_keys, keys);
+
+        let categories = schema["definitions"]["Category"]["enum"]
+            .as_array()
+            .unwrap();
+        for category in categories {
+            let category = category.as_str().unwrap();
+            assert_eq!(Category::from_str(category).unwrap().canonical(), category);
+        }
+    }
 }
@@ -1,4 +1,5 @@
 use crate::bundle;
+use crate::file;
 use crate::terminal;
 use crate::Error;
 
@@ -7,6 +8,7 @@ use std::ffi::OsStr;
 use std::fs;
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
 use uuid::Uuid;
 
 type Package = msi::Package<fs::File>;
@@ -36,6 +38,9 @@ struct ResourceInfo {
     source_path: PathBuf,
     // Relative path from the install dir where this will be installed.
     dest_path: PathBuf,
+    // The database key for this resource's entry in the File table (also
+    // used as its name within the cabinet).
+    file_key: String,
     // The name of this resource file in the filesystem.
     filename: String,
     // The size of this resource file, in bytes.
@@ -52,7 +57,8 @@ struct DirectoryInfo {
     parent_key: String,
     // The name of this directory in the filesystem.
     name: String,
-    // List of files in this directory, not counting subdirectories.
+    // List of File table keys for the files in this directory, not counting
+    // subdirectories.
     files: Vec<String>,
 }
 
@@ -86,7 +92,7 @@ pub fn bundle_project(settings: &bundle::Settings) -> Result<Vec<PathBuf>, Error
     let mut resources = collect_resource_info(settings)?;
     let directories = collect_directory_info(settings, &mut resources)?;
     let cabinets = divide_resources_into_cabinets(resources);
-    generate_resource_cabinets(&mut package, &cabinets)?;
+    generate_resource_cabinets(&mut package, &cabinets, settings.timestamp())?;
 
     // Set up installer database tables:
     terminal::print_progress("Package", "Setting up installer database tables...")?;
@@ -125,6 +131,8 @@ pub fn bundle_project(settings: &bundle::Settings) -> Result<Vec<PathBuf>, Error
     )?;
 
     package.flush()?;
+    drop(package);
+    set_directory_times(&msi_path, settings.timestamp())?;
     Ok(vec![msi_path])
 }
 
@@ -144,16 +152,102 @@ fn new_empty_package(msi_path: &Path) -> Result<Package, Error> {
     Ok(package)
 }
 
-// Generates a GUID for the package, based on `settings.bundle_identifier()`.
+// Generates a GUID for the package, based on `settings.bundle_identifier()`
+// and, when bundling a variant, the variant name, so that each variant gets its
+// own ProductCode and can be installed alongside the others.
 fn generate_package_guid(settings: &bundle::Settings) -> Uuid {
     let namespace = Uuid::from_bytes(UUID_NAMESPACE);
-    Uuid::new_v5(&namespace, settings.bundle_identifier().as_bytes())
+    let name = match settings.variant() {
+        Some(variant) => format!("{}#{variant}", settings.bundle_identifier()),
+        None => settings.bundle_identifier().into_owned(),
+    };
+    Uuid::new_v5(&namespace, name.as_bytes())
+}
+
+// The MSI package is a Compound File Binary (see
+// https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-cfb/), in
+// which the `cfb` crate stamps each storage and stream with the current time.
+// So that the package is reproducible, this rewrites the times in the
+// directory entries of the file at `msi_path`: storages get `timestamp`, and
+// the times the spec requires to be zero (those of streams, and the creation
+// time of the root storage) are cleared.
+fn set_directory_times(msi_path: &Path, timestamp: u64) -> Result<(), Error> {
+    const END_OF_CHAIN: u32 = 0xffff_fffe;
+    const DIR_ENTRY_LEN: usize = 128;
+    const OBJ_TYPE_STORAGE: u8 = 1;
+    const OBJ_TYPE_ROOT: u8 = 5;
+    // The number of 100ns intervals from 1601-01-01 to 1970-01-01.
+    const FILETIME_UNIX_EPOCH: u64 = 116_444_736_000_000_000;
+
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed MSI package");
+    let mut data = fs::read(msi_path)?;
+    let read_u32 = |data: &[u8], offset: usize| -> io::Result<u32> {
+        let bytes = data.get(offset..offset + 4).ok_or_else(invalid)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    let sector_len = 1usize << data.get(30).copied().ok_or_else(invalid)?;
+    let sector_offset = |sector: u32| (sector as usize + 1) * sector_len;
+
+    // Find the sectors of the file allocation table, listed at the end of the
+    // header and then in a chain of DIFAT sectors.
+    let mut fat_sectors = Vec::new();
+    for index in 0..109 {
+        fat_sectors.push(read_u32(&data, 76 + index * 4)?);
+    }
+    let mut difat_sector = read_u32(&data, 68)?;
+    for _ in 0..read_u32(&data, 72)? {
+        let offset = sector_offset(difat_sector);
+        let entries = sector_len / 4 - 1;
+        for index in 0..entries {
+            fat_sectors.push(read_u32(&data, offset + index * 4)?);
+        }
+        difat_sector = read_u32(&data, offset + entries * 4)?;
+    }
+    fat_sectors.truncate(read_u32(&data, 44)? as usize);
+    let next_sector = |data: &[u8], sector: u32| -> io::Result<u32> {
+        let entries = sector_len / 4;
+        let fat_sector = *fat_sectors
+            .get(sector as usize / entries)
+            .ok_or_else(invalid)?;
+        read_u32(
+            data,
+            sector_offset(fat_sector) + (sector as usize % entries) * 4,
+        )
+    };
+
+    let filetime = timestamp
+        .checked_mul(10_000_000)
+        .and_then(|intervals| intervals.checked_add(FILETIME_UNIX_EPOCH))
+        .ok_or_else(|| Error::InvalidSourceDateEpoch(timestamp.to_string()))?
+        .to_le_bytes();
+    let mut sector = read_u32(&data, 48)?;
+    let mut sectors_left = data.len() / sector_len;
+    while sector != END_OF_CHAIN {
+        let offset = sector_offset(sector);
+        // A chain can't have more sectors than the file (unless it loops).
+        if offset + sector_len > data.len() || sectors_left == 0 {
+            return Err(invalid().into());
+        }
+        for entry in data[offset..offset + sector_len].chunks_mut(DIR_ENTRY_LEN) {
+            let (creation_time, modified_time) = match entry[66] {
+                OBJ_TYPE_STORAGE => (filetime, filetime),
+                OBJ_TYPE_ROOT => ([0; 8], filetime),
+                _ => ([0; 8], [0; 8]),
+            };
+            entry[100..108].copy_from_slice(&creation_time);
+            entry[108..116].copy_from_slice(&modified_time);
+        }
+        sector = next_sector(&data, sector)?;
+        sectors_left -= 1;
+    }
+    fs::write(msi_path, data)?;
+    Ok(())
 }
 
 // Populates the summary metadata for the package from the bundle settings.
 fn set_summary_info(package: &mut Package, package_guid: Uuid, settings: &bundle::Settings) {
     let summary_info = package.summary_info_mut();
-    summary_info.set_creation_time_to_now();
+    summary_info.set_creation_time(UNIX_EPOCH + Duration::from_secs(settings.timestamp()));
     summary_info.set_subject(settings.bundle_name().to_string());
     summary_info.set_uuid(package_guid);
     summary_info.set_comments(settings.short_description().to_string());
@@ -201,7 +295,7 @@ fn create_property_table(
             ])
             .row(vec![
                 msi::Value::from("ProductVersion"),
-                msi::Value::from(settings.version_string().to_string()),
+                msi::Value::from(settings.msi_version_string()?),
             ])
             .row(vec![
                 msi::Value::from("DefaultUIFont"),
@@ -231,14 +325,29 @@ fn create_property_table(
 // Returns a list of `ResourceInfo` structs for the binary executable and all
 // the resource files that should be included in the package.
 fn collect_resource_info(settings: &bundle::Settings) -> Result<Vec<ResourceInfo>, Error> {
-    let mut resources = Vec::<ResourceInfo>::new();
-    resources.push(ResourceInfo {
+    let mut resources = vec![ResourceInfo {
         source_path: settings.binary_path().to_path_buf(),
         dest_path: PathBuf::from(settings.binary_name()),
+        file_key: settings.binary_name().to_string(),
         filename: settings.binary_name().to_string(),
         size: settings.binary_path().metadata()?.len(),
         component_key: String::new(),
-    });
+    }];
+    let root_rsrc_dir = PathBuf::from("Resources");
+    for (index, source_path) in settings.resource_files().enumerate() {
+        let source_path = source_path?;
+        let size = source_path.metadata()?.len();
+        let dest_path = root_rsrc_dir.join(file::resource_relpath(&source_path));
+        let filename = dest_path.file_name().unwrap().to_string_lossy().to_string();
+        resources.push(ResourceInfo {
+            source_path,
+            dest_path,
+            file_key: format!("RSRC{index:04}"),
+            filename,
+            size,
+            component_key: String::new(),
+        });
+    }
     Ok(resources)
 }
 
@@ -288,7 +397,7 @@ fn collect_directory_info(
         }
         let directory = dir_map.get_mut(&dir_path).unwrap();
         debug_assert_eq!(directory.key, dir_key);
-        directory.files.push(resource.filename.clone());
+        directory.files.push(resource.file_key.clone());
         resource.component_key = dir_key.to_string();
     }
     Ok(dir_map.into_values().collect())
@@ -296,14 +405,14 @@ fn collect_directory_info(
 
 // Divides up the list of resource into some number of cabinets, subject to a
 // few constraints: 1) no one cabinet will have two resources with the same
-// filename, 2) no one cabinet will have more than `CABINET_MAX_FILES` files
+// file key, 2) no one cabinet will have more than `CABINET_MAX_FILES` files
 // in it, and 3) no one cabinet will contain more than `CABINET_MAX_SIZE`
 // bytes of data (unless that cabinet consists of a single file that is
 // already bigger than that).
 fn divide_resources_into_cabinets(mut resources: Vec<ResourceInfo>) -> Vec<CabinetInfo> {
     let mut cabinets = Vec::new();
     while !resources.is_empty() {
-        let mut filenames = HashSet::<String>::new();
+        let mut file_keys = HashSet::<String>::new();
         let mut total_size = 0;
         let mut leftovers = Vec::<ResourceInfo>::new();
         let mut cabinet = CabinetInfo {
@@ -313,11 +422,11 @@ fn divide_resources_into_cabinets(mut resources: Vec<ResourceInfo>) -> Vec<Cabin
         for resource in resources.into_iter() {
             if cabinet.resources.len() >= CABINET_MAX_FILES
                 || (!cabinet.resources.is_empty() && total_size + resource.size > CABINET_MAX_SIZE)
-                || filenames.contains(&resource.filename)
+                || file_keys.contains(&resource.file_key)
             {
                 leftovers.push(resource);
             } else {
-                filenames.insert(resource.filename.clone());
+                file_keys.insert(resource.file_key.clone());
                 total_size += resource.size;
                 cabinet.resources.push(resource);
             }
@@ -333,7 +442,11 @@ fn divide_resources_into_cabinets(mut resources: Vec<ResourceInfo>) -> Vec<Cabin
 fn generate_resource_cabinets(
     package: &mut Package,
     cabinets: &[CabinetInfo],
+    timestamp: u64,
 ) -> Result<(), Error> {
+    let datetime = time::OffsetDateTime::from_unix_timestamp(timestamp as i64)
+        .map_err(|_| Error::InvalidSourceDateEpoch(timestamp.to_string()))?;
+    let datetime = time::PrimitiveDateTime::new(datetime.date(), datetime.time());
     for cabinet_info in cabinets.iter() {
         let mut builder = cab::CabinetBuilder::new();
         let mut file_map = HashMap::<String, &Path>::new();
@@ -346,9 +459,11 @@ fn generate_resource_cabinets(
             {
                 let resource = &cabinet_info.resources[resource_index];
                 folder_size += resource.size;
-                folder.add_file(resource.filename.as_str());
-                debug_assert!(!file_map.contains_key(&resource.filename));
-                file_map.insert(resource.filename.clone(), &resource.source_path);
+                folder
+                    .add_file(resource.file_key.as_str())
+                    .set_datetime(datetime);
+                debug_assert!(!file_map.contains_key(&resource.file_key));
+                file_map.insert(resource.file_key.clone(), &resource.source_path);
                 resource_index += 1;
             }
         }
@@ -603,7 +718,7 @@ fn create_file_table(package: &mut Package, cabinets: &[CabinetInfo]) -> Result<
     for cabinet in cabinets.iter() {
         for resource in cabinet.resources.iter() {
             rows.push(vec![
-                msi::Value::Str(resource.filename.clone()),
+                msi::Value::Str(resource.file_key.clone()),
                 msi::Value::Str(resource.component_key.clone()),
                 msi::Value::Str(resource.filename.clone()),
                 msi::Value::Int(resource.size as i32),
@@ -1136,3 +1251,40 @@ fn create_app_icon<W: Write>(writer: &mut W, settings: &bundle::Settings) -> Res
     // TODO: Convert from other formats.
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{new_empty_package, set_directory_times};
+    use std::fs;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn directory_times_are_fixed() {
+        let temp_dir = tempdir().unwrap();
+        let mut packages = Vec::new();
+        for name in ["a.msi", "b.msi"] {
+            let msi_path = temp_dir.path().join(name);
+            let mut package = new_empty_package(&msi_path).unwrap();
+            package
+                .write_stream("Example")
+                .unwrap()
+                .write_all(b"example")
+                .unwrap();
+            package.flush().unwrap();
+            drop(package);
+            set_directory_times(&msi_path, 1_700_000_000).unwrap();
+            packages.push(msi_path);
+        }
+        assert_eq!(
+            fs::read(&packages[0]).unwrap(),
+            fs::read(&packages[1]).unwrap()
+        );
+
+        let mut package = msi::open(&packages[0]).unwrap();
+        let mut contents = String::new();
+        std::io::Read::read_to_string(&mut package.read_stream("Example").unwrap(), &mut contents)
+            .unwrap();
+        assert_eq!(contents, "example");
+    }
+}
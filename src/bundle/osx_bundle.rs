@@ -14,8 +14,9 @@
 // See https://developer.apple.com/go/?id=bundle-structure for a full
 // explanation.
 //
-// Currently, cargo-bundle does not support Frameworks, nor does it support placing arbitrary
-// files into the `Contents` directory of the bundle.
+// Resource files listed in the bundle metadata are copied into `Contents/Resources`.
+// Currently, cargo-bundle does not support placing arbitrary files elsewhere in the
+// `Contents` directory of the bundle.
 use crate::bundle;
 use crate::file;
 use crate::image;
@@ -49,6 +50,7 @@ pub fn bundle_project(settings: &bundle::Settings) -> Result<Vec<PathBuf>, Error
 
     create_info_plist(&bundle_directory, bundle_icon_file, settings)?;
     copy_frameworks_to_bundle(&bundle_directory, settings)?;
+    copy_resources_to_bundle(&resources_dir, settings)?;
     copy_binary_to_bundle(&bundle_directory, settings)?;
 
     Ok(vec![app_bundle_path])
@@ -65,12 +67,23 @@ fn copy_binary_to_bundle(
     )
 }
 
+fn copy_resources_to_bundle(
+    resources_dir: &Path,
+    settings: &bundle::Settings,
+) -> Result<(), Error> {
+    for src in settings.resource_files() {
+        let src = src?;
+        let dest = resources_dir.join(file::resource_relpath(&src));
+        file::copy(&src, &dest)?;
+    }
+    Ok(())
+}
+
 fn create_info_plist(
     bundle_dir: &Path,
     bundle_icon_file: Option<PathBuf>,
     settings: &bundle::Settings,
 ) -> Result<(), Error> {
-    let build_number = chrono::Utc::now().format("%Y%m%d.%H%M%S");
     let file = &mut file::create(&bundle_dir.join("Info.plist"))?;
     write!(
         file,
@@ -124,7 +137,7 @@ fn create_info_plist(
     write!(
         file,
         "  <key>CFBundleShortVersionString</key>\n  <string>{}</string>\n",
-        settings.version_string()
+        settings.apple_short_version_string()
     )?;
     if !settings.osx_url_schemes().is_empty() {
         write!(
@@ -152,7 +165,8 @@ fn create_info_plist(
     }
     write!(
         file,
-        "  <key>CFBundleVersion</key>\n  <string>{build_number}</string>\n"
+        "  <key>CFBundleVersion</key>\n  <string>{}</string>\n",
+        settings.osx_bundle_version()
     )?;
     write!(file, "  <key>CSResourcesFileMapped</key>\n  <true/>\n")?;
     if let Some(category) = settings.app_category() {
@@ -184,18 +198,6 @@ fn create_info_plist(
     Ok(())
 }
 
-fn copy_framework_from(dest_dir: &Path, framework: &str, src_dir: &Path) -> Result<bool, Error> {
-    let src_name = format!("{framework}.framework");
-    let src_path = src_dir.join(&src_name);
-
-    if src_path.exists() {
-        file::copy_dir(&src_path, &dest_dir.join(&src_name))?;
-        Ok(true)
-    } else {
-        Ok(false)
-    }
-}
-
 fn copy_frameworks_to_bundle(
     bundle_directory: &Path,
     settings: &bundle::Settings,
@@ -210,39 +212,45 @@ fn copy_frameworks_to_bundle(
     fs::create_dir_all(bundle_directory)?;
 
     for framework in frameworks.iter() {
+        let src_path = locate_framework(framework)?;
+        let dest_path = dest_dir.join(src_path.file_name().unwrap());
         if framework.ends_with(".framework") {
-            let src_path = PathBuf::from(framework);
-            let src_name = src_path.file_name().unwrap();
-            file::copy(&src_path, &dest_dir.join(src_name))?;
-            continue;
-        } else if framework.contains('/') {
-            return Err(Error::MacosFrameworkNotValid(framework.clone()));
+            file::copy(&src_path, &dest_path)?;
+        } else {
+            file::copy_dir(&src_path, &dest_path)?;
         }
+    }
 
-        if let Some(home_dir) = dirs::home_dir() {
-            if copy_framework_from(&dest_dir, framework, &home_dir.join("Library/Frameworks/"))? {
-                continue;
-            }
-        }
-        if copy_framework_from(&dest_dir, framework, &PathBuf::from("/Library/Frameworks/"))?
-            || copy_framework_from(
-                &dest_dir,
-                framework,
-                &PathBuf::from("/Network/Library/Frameworks/"),
-            )?
-            || copy_framework_from(
-                &dest_dir,
-                framework,
-                &PathBuf::from("/System/Library/Frameworks/"),
-            )?
-        {
-            continue;
-        }
+    Ok(())
+}
 
-        return Err(Error::MacosFrameworkNotFound(framework.clone()));
+/// Returns the path of the framework named in the `osx_frameworks` setting:
+/// either a path to a `.framework` directory, or the name of a framework in
+/// one of the standard framework directories.
+pub(crate) fn locate_framework(framework: &str) -> Result<PathBuf, Error> {
+    if framework.ends_with(".framework") {
+        let src_path = PathBuf::from(framework);
+        if src_path.exists() {
+            return Ok(src_path);
+        }
+        return Err(Error::MacosFrameworkNotFound(framework.to_string()));
+    } else if framework.contains('/') {
+        return Err(Error::MacosFrameworkNotValid(framework.to_string()));
     }
 
-    Ok(())
+    let src_name = format!("{framework}.framework");
+    let mut src_dirs = Vec::new();
+    if let Some(home_dir) = dirs::home_dir() {
+        src_dirs.push(home_dir.join("Library/Frameworks/"));
+    }
+    src_dirs.push(PathBuf::from("/Library/Frameworks/"));
+    src_dirs.push(PathBuf::from("/Network/Library/Frameworks/"));
+    src_dirs.push(PathBuf::from("/System/Library/Frameworks/"));
+    src_dirs
+        .into_iter()
+        .map(|src_dir| src_dir.join(&src_name))
+        .find(|src_path| src_path.exists())
+        .ok_or_else(|| Error::MacosFrameworkNotFound(framework.to_string()))
 }
 
 /// Given a list of icon files, try to produce an ICNS file in the resources
@@ -0,0 +1,335 @@
+// Translates the application's version (normally a semver version, such as
+// `1.2.0-beta.1`) into the version formats of each bundle format, which can't
+// all represent semver versions as they are:
+//
+// * Debian versions are `[EPOCH:]UPSTREAM[-REVISION]`, compared so that `~`
+//   sorts before anything (even the end of the version), so a pre-release
+//   `1.2.0-beta.1` becomes `1.2.0~beta.1`, which sorts before `1.2.0`.
+// * RPM versions are `VERSION-RELEASE`, where neither part may contain a `-`,
+//   and `~` sorts as in Debian versions, so `1.2.0-beta.1` becomes
+//   `1.2.0~beta.1`.
+// * MSI product versions are `MAJOR.MINOR.BUILD`, at most 255.255.65535.
+// * Apple bundle versions (`CFBundleVersion` and
+//   `CFBundleShortVersionString`) are up to three period-separated integers.
+
+use crate::Error;
+
+use cargo_metadata::semver;
+use std::cmp::Ordering;
+
+/// The largest value of each part of an MSI product version.
+const MSI_VERSION_LIMITS: [u64; 3] = [255, 255, 65535];
+
+/// Returns the upstream part of the Debian version for `version`:
+/// pre-release and build metadata are kept after a `~` and a `+`
+/// respectively.  A version that isn't semver is used as it is.
+pub(crate) fn debian_upstream_version(version: &str) -> String {
+    match semver::Version::parse(version) {
+        Ok(version) => {
+            let mut upstream = format!("{}.{}.{}", version.major, version.minor, version.patch);
+            // A `-` in the upstream version would be taken as the start of
+            // the revision.
+            if !version.pre.is_empty() {
+                upstream.push('~');
+                upstream.push_str(&version.pre.as_str().replace('-', "."));
+            }
+            if !version.build.is_empty() {
+                upstream.push('+');
+                upstream.push_str(&version.build.as_str().replace('-', "."));
+            }
+            upstream
+        }
+        Err(_) => version.to_string(),
+    }
+}
+
+/// Returns the Debian version made of `upstream` and, if given, `epoch` and
+/// `revision`.
+pub(crate) fn debian_version(upstream: &str, epoch: Option<u32>, revision: Option<&str>) -> String {
+    let mut version = upstream.to_string();
+    if let Some(epoch) = epoch {
+        version = format!("{epoch}:{version}");
+    }
+    if let Some(revision) = revision {
+        version = format!("{version}-{revision}");
+    }
+    version
+}
+
+/// Returns true if `version` is a valid Debian version.  See
+/// https://www.debian.org/doc/debian-policy/ch-controlfields.html#version
+pub(crate) fn is_debian_version(version: &str) -> bool {
+    let (epoch, rest) = match version.split_once(':') {
+        Some((epoch, rest)) => (Some(epoch), rest),
+        None => (None, version),
+    };
+    let (upstream, revision) = match rest.rsplit_once('-') {
+        Some((upstream, revision)) => (upstream, Some(revision)),
+        None => (rest, None),
+    };
+    let epoch_valid =
+        epoch.is_none_or(|epoch| !epoch.is_empty() && epoch.chars().all(|c| c.is_ascii_digit()));
+    let upstream_valid = upstream.starts_with(|c: char| c.is_ascii_digit())
+        && upstream
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ".+~-".contains(c));
+    let revision_valid = revision.is_none_or(|revision| {
+        !revision.is_empty()
+            && revision
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || ".+~".contains(c))
+    });
+    epoch_valid && upstream_valid && revision_valid
+}
+
+/// Returns the RPM version for `version`: written as the Debian upstream
+/// version, with any other `-` (which RPM versions can't contain) replaced by
+/// a `~`.
+pub(crate) fn rpm_version(version: &str) -> String {
+    debian_upstream_version(version).replace('-', "~")
+}
+
+/// Returns true if `part` is a valid RPM version or release.
+pub(crate) fn is_rpm_version_part(part: &str) -> bool {
+    !part.is_empty()
+        && part
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "._+~^".contains(c))
+}
+
+/// Compares two Debian versions the way `dpkg --compare-versions` does.
+pub(crate) fn compare_debian_versions(a: &str, b: &str) -> Ordering {
+    fn split(version: &str) -> (u64, &str, &str) {
+        let (epoch, rest) = match version.split_once(':') {
+            Some((epoch, rest)) => (epoch.parse().unwrap_or(0), rest),
+            None => (0, version),
+        };
+        match rest.rsplit_once('-') {
+            Some((upstream, revision)) => (epoch, upstream, revision),
+            None => (epoch, rest, ""),
+        }
+    }
+    let (a_epoch, a_upstream, a_revision) = split(a.trim());
+    let (b_epoch, b_upstream, b_revision) = split(b.trim());
+    a_epoch
+        .cmp(&b_epoch)
+        .then_with(|| compare_version_parts(a_upstream, b_upstream))
+        .then_with(|| compare_version_parts(a_revision, b_revision))
+}
+
+/// Compares the upstream versions or revisions of two Debian versions: runs
+/// of non-digits are compared character by character (`~` sorts before
+/// anything, even the end of the part, and letters before other characters),
+/// and runs of digits numerically.
+fn compare_version_parts(a: &str, b: &str) -> Ordering {
+    fn order(c: Option<&u8>) -> i32 {
+        match c {
+            None => 0,
+            Some(b'~') => -1,
+            Some(c) if c.is_ascii_digit() => 0,
+            Some(c) if c.is_ascii_alphabetic() => i32::from(*c),
+            Some(c) => i32::from(*c) + 256,
+        }
+    }
+    fn is_digit(part: &[u8], index: usize) -> bool {
+        part.get(index).is_some_and(u8::is_ascii_digit)
+    }
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        while (i < a.len() && !is_digit(a, i)) || (j < b.len() && !is_digit(b, j)) {
+            let (a_order, b_order) = (order(a.get(i)), order(b.get(j)));
+            if a_order != b_order {
+                return a_order.cmp(&b_order);
+            }
+            i += 1;
+            j += 1;
+        }
+        while a.get(i) == Some(&b'0') {
+            i += 1;
+        }
+        while b.get(j) == Some(&b'0') {
+            j += 1;
+        }
+        let mut first_difference = Ordering::Equal;
+        while is_digit(a, i) && is_digit(b, j) {
+            if first_difference == Ordering::Equal {
+                first_difference = a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+        if is_digit(a, i) {
+            return Ordering::Greater;
+        }
+        if is_digit(b, j) {
+            return Ordering::Less;
+        }
+        if first_difference != Ordering::Equal {
+            return first_difference;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Returns the MSI product version for `version`: its first three numeric
+/// parts (missing parts are zero), without any pre-release or build metadata.
+/// Fails if a part is out of range, or the version doesn't start with numeric
+/// parts.
+pub(crate) fn msi_version(version: &str) -> Result<String, Error> {
+    let invalid = || Error::InvalidMsiVersion(version.to_string());
+    let parts = numeric_parts(version).ok_or_else(invalid)?;
+    if parts.len() > MSI_VERSION_LIMITS.len()
+        || parts
+            .iter()
+            .zip(MSI_VERSION_LIMITS)
+            .any(|(&part, limit)| part > limit)
+    {
+        return Err(invalid());
+    }
+    Ok(pad_parts(&parts))
+}
+
+/// Returns the Apple bundle version for `version`: up to three of the numbers
+/// it starts with (missing parts are zero), without any pre-release or build
+/// metadata, or other text, after them (e.g. `1.2rc1` becomes `1.2.0`).  A
+/// version that doesn't start with a number is used as it is, for `lint` to
+/// report.
+pub(crate) fn apple_version(version: &str) -> String {
+    let release = version.split(['-', '+']).next().unwrap();
+    let mut parts = Vec::new();
+    for part in release.split('.').take(3) {
+        let digits = part.len() - part.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        match part[..digits].parse::<u64>() {
+            Ok(number) => parts.push(number),
+            Err(_) => break,
+        }
+        if digits < part.len() {
+            break;
+        }
+    }
+    if parts.is_empty() {
+        return version.to_string();
+    }
+    pad_parts(&parts)
+}
+
+/// Returns true if `version` is a valid Apple bundle version.
+pub(crate) fn is_apple_version(version: &str) -> bool {
+    let parts: Vec<&str> = version.split('.').collect();
+    parts.len() <= 3
+        && parts
+            .iter()
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Returns the numeric parts at the start of `version`, before any
+/// pre-release (`-`) or build metadata (`+`).
+fn numeric_parts(version: &str) -> Option<Vec<u64>> {
+    let release = version.split(['-', '+']).next().unwrap();
+    release
+        .split('.')
+        .map(|part| part.parse::<u64>().ok())
+        .collect()
+}
+
+/// Joins numeric version parts, padded with zeros to three parts.
+fn pad_parts(parts: &[u64]) -> String {
+    let mut parts: Vec<String> = parts.iter().map(u64::to_string).collect();
+    parts.resize(3, "0".to_string());
+    parts.join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        apple_version, compare_debian_versions, debian_upstream_version, debian_version,
+        is_apple_version, is_debian_version, is_rpm_version_part, msi_version, rpm_version,
+    };
+    use std::cmp::Ordering;
+
+    #[test]
+    fn debian_versions() {
+        assert_eq!(debian_upstream_version("1.2.0"), "1.2.0");
+        assert_eq!(debian_upstream_version("1.2.0-beta.1"), "1.2.0~beta.1");
+        assert_eq!(debian_upstream_version("1.2.0-rc-1"), "1.2.0~rc.1");
+        assert_eq!(debian_upstream_version("1.2.0+git.5"), "1.2.0+git.5");
+        assert_eq!(debian_upstream_version("1.2"), "1.2");
+        assert_eq!(debian_version("1.2.0", None, None), "1.2.0");
+        assert_eq!(
+            debian_version("1.2.0~beta.1", Some(2), Some("1ubuntu1")),
+            "2:1.2.0~beta.1-1ubuntu1"
+        );
+
+        assert!(is_debian_version("1.2.0~beta.1"));
+        assert!(is_debian_version("2:1.2.0-1ubuntu1"));
+        assert!(is_debian_version("1.2-rc-1-3"));
+        assert!(!is_debian_version("v1.2.0"));
+        assert!(!is_debian_version("x:1.2.0"));
+        assert!(!is_debian_version("1.2.0-"));
+        assert!(!is_debian_version("1.2.0_1"));
+    }
+
+    #[test]
+    fn debian_version_order() {
+        let ordered = [
+            "1.0~beta1",
+            "1.0",
+            "1.0-1",
+            "1.0-1ubuntu1",
+            "1.0+git1",
+            "1.0.1",
+            "1.2",
+            "1.10",
+            "1:0.9",
+        ];
+        for (i, a) in ordered.iter().enumerate() {
+            for (j, b) in ordered.iter().enumerate() {
+                assert_eq!(compare_debian_versions(a, b), i.cmp(&j), "{a} vs {b}");
+            }
+        }
+        assert_eq!(compare_debian_versions("1.01", "1.1"), Ordering::Equal);
+        assert_eq!(compare_debian_versions("0:1.0", "1.0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn rpm_versions() {
+        assert_eq!(rpm_version("1.2.0"), "1.2.0");
+        assert_eq!(rpm_version("1.2.0-rc-1+git.5"), "1.2.0~rc.1+git.5");
+        assert_eq!(rpm_version("2024-05"), "2024~05");
+
+        assert!(is_rpm_version_part("1.2.0~beta.1"));
+        assert!(is_rpm_version_part("1_el9"));
+        assert!(!is_rpm_version_part("1-2"));
+        assert!(!is_rpm_version_part(""));
+    }
+
+    #[test]
+    fn msi_versions() {
+        assert_eq!(msi_version("1.2.3").unwrap(), "1.2.3");
+        assert_eq!(msi_version("1.2.3-beta.1+git.5").unwrap(), "1.2.3");
+        assert_eq!(msi_version("1.2").unwrap(), "1.2.0");
+        assert_eq!(msi_version("255.255.65535").unwrap(), "255.255.65535");
+        assert!(msi_version("256.0.0").is_err());
+        assert!(msi_version("1.0.65536").is_err());
+        assert!(msi_version("1.2.3.4").is_err());
+        assert!(msi_version("nightly").is_err());
+    }
+
+    #[test]
+    fn apple_versions() {
+        assert_eq!(apple_version("1.2.3-beta.1"), "1.2.3");
+        assert_eq!(apple_version("10"), "10.0.0");
+        assert_eq!(apple_version("1.2.3.4"), "1.2.3");
+        assert_eq!(apple_version("2.0.0-rc.1+build.5"), "2.0.0");
+        assert_eq!(apple_version("1.2rc1"), "1.2.0");
+        assert_eq!(apple_version("1.2.x"), "1.2.0");
+        assert_eq!(apple_version("3.1-nightly"), "3.1.0");
+        assert_eq!(apple_version("nightly"), "nightly");
+
+        assert!(is_apple_version("1.2.3"));
+        assert!(is_apple_version("42"));
+        assert!(!is_apple_version("1.2.3.4"));
+        assert!(!is_apple_version("1.2.3-beta.1"));
+    }
+}
@@ -1,4 +1,5 @@
 use std::fmt;
+use std::str::FromStr;
 
 const CONFIDENCE_THRESHOLD: f64 = 0.8;
 const OSX_APP_CATEGORY_PREFIX: &str = "public.app-category.";
@@ -50,10 +51,12 @@ pub enum Category {
     Weather,
 }
 
-impl Category {
+impl FromStr for Category {
+    type Err = Option<&'static str>;
+
     /// Given a string, returns the `AppCategory` it refers to, or the closest
     /// string that the user might have intended (if any).
-    pub fn from_str(input: &str) -> Result<Category, Option<&'static str>> {
+    fn from_str(input: &str) -> Result<Category, Option<&'static str>> {
         // Canonicalize input:
         let mut input = input.to_ascii_lowercase();
         if input.starts_with(OSX_APP_CATEGORY_PREFIX) {
@@ -77,10 +80,12 @@ impl Category {
         }
         Err(best_category.map(Category::canonical))
     }
+}
 
+impl Category {
     /// Map an AppCategory to the string we recommend to use in Cargo.toml if
     /// the users misspells the category name.
-    fn canonical(self) -> &'static str {
+    pub(crate) fn canonical(self) -> &'static str {
         match self {
             Category::Business => "Business",
             Category::DeveloperTool => "Developer Tool",
@@ -226,6 +231,30 @@ impl<'d> serde::Deserialize<'d> for Category {
     }
 }
 
+impl schemars::JsonSchema for Category {
+    fn schema_name() -> String {
+        "Category".to_string()
+    }
+
+    /// The canonical names of the categories.  Other spellings (such as
+    /// `"public.app-category.developer-tools"`) are also accepted when parsing,
+    /// but aren't listed.
+    fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        let mut names: Vec<&str> = CATEGORY_STRINGS
+            .iter()
+            .map(|&(_, category)| category.canonical())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        schemars::schema::SchemaObject {
+            instance_type: Some(schemars::schema::InstanceType::String.into()),
+            enum_values: Some(names.into_iter().map(serde_json::Value::from).collect()),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
 struct AppCategoryVisitor {
     did_you_mean: Option<&'static str>,
 }
@@ -336,6 +365,7 @@ const CATEGORY_STRINGS: &[(&str, Category)] = &[
 #[cfg(test)]
 mod tests {
     use super::Category;
+    use std::str::FromStr;
 
     #[test]
     fn category_from_string_ok() {
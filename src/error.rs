@@ -6,6 +6,12 @@ pub enum Error {
     #[error("unsupported bundle format: {0}")]
     UnsupportedBundle(String),
 
+    #[error("failed to create {0} bundle for package '{1}': {2}")]
+    BundleFailed(&'static str, String, Box<Error>),
+
+    #[error("failed to create bundles: {0}")]
+    BundlesFailed(String),
+
     #[error("`debug` profile is reserved")]
     DebugProfileIsReserved,
 
@@ -27,24 +33,111 @@ pub enum Error {
     #[error("metadate error: {0}")]
     Metadata(#[from] cargo_metadata::Error),
 
+    #[error("invalid target in bundle metadata: {0}")]
+    TargetCfg(#[from] ::cargo_platform::ParseError),
+
     #[error("target error: {0}")]
     Target(#[from] ::target_build_utils::Error),
 
     #[error("terminal error: {0}")]
     Term(#[from] ::term::Error),
 
+    #[error("toml error: {0}")]
+    TomlEdit(#[from] ::toml_edit::TomlError),
+
     #[error("toml error: {0}")]
     Toml(#[from] ::toml::de::Error),
 
     #[error("walkdir error: {0}")]
     Walkdir(#[from] ::walkdir::Error),
 
+    #[error("zip error: {0}")]
+    Zip(#[from] ::zip::result::ZipError),
+
+    #[error("`rustc --print cfg` failed with exit status: {0}")]
+    RustcCfgFailed(process::ExitStatus),
+
     #[error("`cargo build` failed with exist status: {0}")]
     BuildFailed(process::ExitStatus),
 
     #[error("unable to find root package")]
     RootPackageNotFound,
 
+    #[error("package '{0}' is not a member of the workspace")]
+    PackageNotFound(String),
+
+    #[error("no package in workspace has [package.metadata.bundle] section")]
+    NoBundlePackages,
+
+    #[error("`--binary` can only be used when bundling a single package")]
+    BinaryForMultiplePackages,
+
+    #[error("no `--binary` was given for target {0}")]
+    BinaryNotGivenForTarget(String),
+
+    #[error(
+        "unknown bundle setting `{0}` at {1}{}",
+        .2.map(|key| format!(" (did you mean `{key}`?)")).unwrap_or_default()
+    )]
+    UnknownSetting(String, String, Option<&'static str>),
+
+    #[error("{0} already has a [package.metadata.bundle] section (use --force to replace it)")]
+    BundleSectionExists(PathBuf),
+
+    #[error("lint found {0} error(s) and {1} warning(s)")]
+    LintFailed(usize, usize),
+
+    #[error("invalid config override `{0}`: {1}")]
+    InvalidConfig(String, String),
+
+    #[error("package '{1}' has no [package.metadata.bundle.variant.{0}] section")]
+    VariantNotFound(String, String),
+
+    #[error("`{0}.workspace = true` is set, but [workspace.metadata.bundle] has no `{0}`")]
+    WorkspaceSettingNotFound(String),
+
+    #[error("`no_inherit` names `{0}`, which is not a list setting")]
+    NotAListField(String),
+
+    #[error(
+        "version \"{0}\" can't be used as an MSI product version, which must be \
+         MAJOR.MINOR.BUILD, at most 255.255.65535 (set `msi_version` to override it)"
+    )]
+    InvalidMsiVersion(String),
+
+    #[error("rpm {0} \"{1}\" may only contain letters, digits, `.`, `_`, `+`, `~` and `^`")]
+    InvalidRpmVersion(&'static str, String),
+
+    #[error("SOURCE_DATE_EPOCH must be a number of seconds from 0 to 4294967295, not \"{0}\"")]
+    InvalidSourceDateEpoch(String),
+
+    #[error("invalid deb control field: {0}")]
+    InvalidControlField(String),
+
+    #[error(
+        "no package in the dpkg database at {1} contains the shared library {0} \
+         (add it to `deb_shlibdeps_overrides`)"
+    )]
+    SharedLibraryNotFound(String, PathBuf),
+
+    #[error("no dpkg database found at {0} (set `deb_dpkg_dir`, or turn off `deb_shlibdeps`)")]
+    DpkgDatabaseNotFound(PathBuf),
+
+    #[error("elf error: {0}")]
+    Elf(#[from] ::goblin::error::Error),
+
+    #[error("invalid `deb_compression_level`: {0}")]
+    InvalidCompressionLevel(String),
+
+    #[error(
+        "{0} compression needs cargo-bundle to be built with the `{0}` feature \
+         (`cargo install cargo-bundle --features {0}`)"
+    )]
+    CompressionFeatureMissing(&'static str),
+
+    #[error("failed to read deb maintainer script {0}: {1}")]
+    MaintainerScript(PathBuf, std::io::Error),
+
     #[error("no `bin` target is found in package '{0}'")]
     NoBinTargetFound(String),
 
@@ -57,6 +150,12 @@ pub enum Error {
     #[error("could not locate framework: {0}")]
     MacosFrameworkNotFound(String),
 
+    #[error("`appimage_runtime` must be set to the path of an AppImage runtime")]
+    AppImageRuntimeNotConfigured,
+
+    #[error("AppImage runtime is not an ELF executable: {0}")]
+    AppImageRuntimeNotValid(PathBuf),
+
     #[error("no usable icon files found")]
     UsableIconFilesNotFound,
 
@@ -1,8 +1,9 @@
 use crate::Error;
 
+use sha2::{Digest, Sha256};
 use std::fs::{self, File};
-use std::io::{self, BufWriter};
-use std::path::Path;
+use std::io::{self, BufWriter, Read};
+use std::path::{Component, Path, PathBuf};
 
 /// Creates a new file at the given path, creating any parent directories as
 /// needed.
@@ -84,9 +85,46 @@ pub fn copy_dir(from: &Path, to: &Path) -> Result<(), Error> {
     Ok(())
 }
 
+/// Given a path to a resource file, returns the relative path from the bundle
+/// resources directory where that resource file should be placed.  Absolute
+/// paths and `..` components are mapped to `_root_` and `_up_` directories, so
+/// that resources can never escape the resources directory.
+pub fn resource_relpath(path: &Path) -> PathBuf {
+    let mut dest = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) => {}
+            Component::RootDir => dest.push("_root_"),
+            Component::CurDir => {}
+            Component::ParentDir => dest.push("_up_"),
+            Component::Normal(string) => dest.push(string),
+        }
+    }
+    dest
+}
+
+/// Compute the hex SHA-256 digest of the given file.
+pub fn generate_sha256sum(file_path: &Path) -> Result<String, Error> {
+    let mut file = File::open(file_path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 8192];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::create;
+    use super::{create, resource_relpath};
 
     use std::io::Write;
     use std::path::PathBuf;
@@ -103,6 +141,27 @@ mod tests {
         assert!(tmp.path().join("parent/file.txt").is_file());
     }
 
+    #[test]
+    fn resource_relative_paths() {
+        assert_eq!(
+            resource_relpath(&PathBuf::from("./data/images/button.png")),
+            PathBuf::from("data/images/button.png")
+        );
+        assert_eq!(
+            resource_relpath(&PathBuf::from("../../data/images/button.png")),
+            PathBuf::from("_up_/_up_/data/images/button.png")
+        );
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn resource_relative_paths_from_root() {
+        assert_eq!(
+            resource_relpath(&PathBuf::from("/home/ferris/crab.png")),
+            PathBuf::from("_root_/home/ferris/crab.png")
+        );
+    }
+
     #[test]
     #[cfg(not(windows))]
     fn copy_dir_with_symlinks() {
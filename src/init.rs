@@ -0,0 +1,254 @@
+// Scaffolds a `[package.metadata.bundle]` section for `cargo bundle init`.
+// The settings are proposed from the package's own metadata, and written into
+// its `Cargo.toml` with `toml_edit`, so that the rest of the file (comments,
+// formatting and key order) is left as it was.
+
+use crate::bundle;
+use crate::terminal;
+use crate::{Category, Error};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use toml_edit::{value, Array, DocumentMut, Item, Table};
+use walkdir::WalkDir;
+
+/// Directories (relative to the package) that are searched for icon files.
+const ICON_DIRS: &[&str] = &[
+    "",
+    "icons",
+    "icon",
+    "assets",
+    "res",
+    "resources",
+    "images",
+    "img",
+    "data",
+];
+
+/// Writes a `[package.metadata.bundle]` section, proposed from the package's
+/// metadata, into the `Cargo.toml` of the named package (or the root package,
+/// if no name is given), and returns the path of the `Cargo.toml`.  Fails if
+/// the package already has a bundle section, unless `force` is set, in which
+/// case the existing section is replaced.
+pub fn init(current_dir: &Path, package: Option<&str>, force: bool) -> Result<PathBuf, Error> {
+    let metadata = bundle::load_metadata(current_dir)?;
+    let package = match package {
+        Some(name) => metadata
+            .workspace_members
+            .iter()
+            .map(|package_id| &metadata[package_id])
+            .find(|package| package.name == name)
+            .ok_or_else(|| Error::PackageNotFound(name.to_string()))?,
+        None => metadata.root_package().ok_or(Error::RootPackageNotFound)?,
+    };
+    let manifest_path = package.manifest_path.clone().into_std_path_buf();
+    if package.metadata.get("bundle").is_some() && !force {
+        return Err(Error::BundleSectionExists(manifest_path));
+    }
+
+    let package_dir = manifest_path.parent().unwrap();
+    let table = propose_bundle_table(package, &find_icon_files(package_dir)?);
+    let manifest = fs::read_to_string(&manifest_path)?;
+    fs::write(&manifest_path, insert_bundle_table(&manifest, table)?)?;
+    terminal::print_progress(
+        "Created",
+        &format!(
+            "[package.metadata.bundle] section in {}",
+            manifest_path.display()
+        ),
+    )?;
+    if package.description.is_none() {
+        terminal::print_warning(
+            "the package has no `description`; add one, or a `short_description` bundle setting",
+        )?;
+    }
+    Ok(manifest_path)
+}
+
+/// Proposes the bundle settings for a package.
+fn propose_bundle_table(package: &cargo_metadata::Package, icon_files: &[String]) -> Table {
+    let mut table = Table::new();
+    table["name"] = value(package.name.as_str());
+    table["identifier"] = value(propose_identifier(package));
+    if !icon_files.is_empty() {
+        table["icon"] = value(icon_files.iter().collect::<Array>());
+    }
+    if let Some(category) = propose_category(package) {
+        table["category"] = value(category.canonical());
+    }
+    if !package.authors.is_empty() {
+        let authors: Vec<&str> = package
+            .authors
+            .iter()
+            .map(|author| author.split('<').next().unwrap().trim())
+            .collect();
+        table["copyright"] = value(format!("Copyright (c) {}", authors.join(", ")));
+    }
+    table
+}
+
+/// Proposes a reverse-DNS identifier from the package's repository URL
+/// (e.g. `com.github.jdoe.example` for `https://github.com/jdoe/example`) or
+/// the host of its homepage URL.
+fn propose_identifier(package: &cargo_metadata::Package) -> String {
+    let (url, use_path) = match (&package.repository, &package.homepage) {
+        (Some(repository), _) => (repository.as_str(), true),
+        (None, Some(homepage)) => (homepage.as_str(), false),
+        (None, None) => ("", false),
+    };
+    let url = url.split("://").last().unwrap();
+    let mut parts = url.split('/').filter(|part| !part.is_empty());
+    let host = parts.next().unwrap_or("");
+    let host = host.split(':').next().unwrap();
+    let host = host.strip_prefix("www.").unwrap_or(host);
+
+    let mut identifier: Vec<String> = host.rsplit('.').map(identifier_part).collect();
+    if identifier.len() < 2 {
+        identifier = vec!["com".to_string(), "example".to_string()];
+    }
+    if use_path {
+        for part in parts {
+            identifier.push(identifier_part(part.trim_end_matches(".git")));
+        }
+    }
+    let name = identifier_part(&package.name);
+    if identifier.last() != Some(&name) {
+        identifier.push(name);
+    }
+    identifier.retain(|part| !part.is_empty());
+    identifier.join(".")
+}
+
+/// Converts a piece of a URL into a part of a reverse-DNS identifier, which
+/// may only contain letters, digits and `-`.
+fn identifier_part(part: &str) -> String {
+    part.chars()
+        .map(|c| match c {
+            'A'..='Z' => c.to_ascii_lowercase(),
+            'a'..='z' | '0'..='9' | '-' => c,
+            _ => '-',
+        })
+        .collect()
+}
+
+/// Proposes an app category from the package's crates.io categories (e.g.
+/// `development-tools`) or keywords.
+fn propose_category(package: &cargo_metadata::Package) -> Option<Category> {
+    let categories = package
+        .categories
+        .iter()
+        .flat_map(|category| category.rsplit("::"));
+    categories
+        .chain(package.keywords.iter().map(String::as_str))
+        .find_map(|name| match Category::from_str(name) {
+            Ok(category) => Some(category),
+            Err(did_you_mean) => did_you_mean.and_then(|name| Category::from_str(name).ok()),
+        })
+}
+
+/// Returns the paths (relative to the package directory) of files that look
+/// like app icons.
+fn find_icon_files(package_dir: &Path) -> Result<Vec<String>, Error> {
+    let mut icon_files = Vec::new();
+    for dir in ICON_DIRS {
+        let dir = package_dir.join(dir);
+        if !dir.is_dir() {
+            continue;
+        }
+        let entries = WalkDir::new(&dir)
+            .max_depth(2)
+            .sort_by(|a, b| a.file_name().cmp(b.file_name()));
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            let is_image = matches!(
+                path.extension().and_then(|ext| ext.to_str()),
+                Some("png" | "icns" | "ico")
+            );
+            let in_icon_dir = path
+                .strip_prefix(package_dir)
+                .unwrap()
+                .components()
+                .any(|dir| dir.as_os_str().to_string_lossy().contains("icon"));
+            let stem = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_ascii_lowercase())
+                .unwrap_or_default();
+            let is_icon_file = entry.file_type().is_file()
+                && is_image
+                && (in_icon_dir || stem.contains("icon") || stem.contains("logo"));
+            if !is_icon_file || path.starts_with(package_dir.join("target")) {
+                continue;
+            }
+            let rel_path = path.strip_prefix(package_dir).unwrap();
+            let rel_path = rel_path.to_string_lossy().replace('\\', "/");
+            if !icon_files.contains(&rel_path) {
+                icon_files.push(rel_path);
+            }
+        }
+    }
+    Ok(icon_files)
+}
+
+/// Inserts (or replaces) the `[package.metadata.bundle]` table in the text of
+/// a `Cargo.toml`, leaving the rest of the text unchanged.
+fn insert_bundle_table(manifest: &str, bundle_table: Table) -> Result<String, Error> {
+    let mut document = DocumentMut::from_str(manifest)?;
+    let package = document["package"]
+        .as_table_mut()
+        .ok_or(Error::RootPackageNotFound)?;
+    let metadata = package
+        .entry("metadata")
+        .or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        })
+        .as_table_mut()
+        .ok_or(Error::RootPackageNotFound)?;
+    metadata.insert("bundle", Item::Table(bundle_table));
+    Ok(document.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{identifier_part, insert_bundle_table};
+    use toml_edit::{value, Table};
+
+    #[test]
+    fn identifier_parts() {
+        assert_eq!(identifier_part("Example_App"), "example-app");
+        assert_eq!(identifier_part("my-app2"), "my-app2");
+    }
+
+    #[test]
+    fn insert_preserves_formatting() {
+        let manifest = "\
+            # The package.\n\
+            [package]\n\
+            name    = \"example\"   # aligned\n\
+            version = \"0.1.0\"\n\
+            \n\
+            [dependencies]\n\
+            serde = \"1\"\n";
+        let mut table = Table::new();
+        table["name"] = value("example");
+        table["identifier"] = value("com.example.example");
+        assert_eq!(
+            insert_bundle_table(manifest, table).unwrap(),
+            "\
+            # The package.\n\
+            [package]\n\
+            name    = \"example\"   # aligned\n\
+            version = \"0.1.0\"\n\
+            \n\
+            [package.metadata.bundle]\n\
+            name = \"example\"\n\
+            identifier = \"com.example.example\"\n\
+            \n\
+            [dependencies]\n\
+            serde = \"1\"\n"
+        );
+    }
+}
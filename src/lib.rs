@@ -0,0 +1,40 @@
+//! Wrap Rust executables in OS-specific app bundles.
+//!
+//! This is the library behind the `cargo bundle` subcommand, for driving
+//! bundling from other tools (such as an `xtask`).  `SettingsBuilder` takes the
+//! same options as the command line and reads each package's
+//! `[package.metadata.bundle]` section; `Bundler` then builds the binaries and
+//! creates the bundles:
+//!
+//! ```no_run
+//! use cargo_bundle::{BuildArtifact, Bundle, Bundler, SettingsBuilder};
+//!
+//! let settings = SettingsBuilder::new(".")
+//!     .artifact(BuildArtifact::Bin("my-app".to_string()))
+//!     .profile("release")
+//!     .format(Bundle::LinuxDeb)
+//!     .format(Bundle::LinuxRpm)
+//!     .build()?;
+//! for artifact in Bundler::new(settings).run()?.artifacts {
+//!     println!("{}: {}", artifact.format.short_name(), artifact.path.display());
+//! }
+//! # Ok::<(), cargo_bundle::Error>(())
+//! ```
+
+mod build;
+mod bundle;
+mod category;
+mod error;
+mod file;
+mod image;
+mod init;
+mod terminal;
+
+pub use bundle::{
+    lint, metadata_schema, Artifact, BuildArtifact, Bundle, BundleEvent, BundleFailure,
+    BundleReport, Bundler, DebCompression, DebPriority, Diagnostic, IosDeviceFamily,
+    IosOrientation, Settings, SettingsBuilder, Severity,
+};
+pub use category::Category;
+pub use error::Error;
+pub use init::init;
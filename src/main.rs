@@ -1,79 +1,20 @@
-mod bundle;
-mod category;
-mod error;
-mod file;
-mod image;
-mod terminal;
+mod output;
 
-use bundle::Bundle;
-use category::Category;
-use error::Error;
+use cargo_bundle::{
+    BuildArtifact, Bundle, BundleEvent, Bundler, DebCompression, Error, SettingsBuilder, Severity,
+};
 
-use clap::{App, AppSettings, Arg, SubCommand};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use serde_json::json;
 use std::env;
-use std::ffi::OsString;
-use std::process;
-
-fn build_project_if_unbuilt(settings: &bundle::Settings) -> Result<(), Error> {
-    if std::env::var("CARGO_BUNDLE_SKIP_BUILD").is_ok() {
-        return Ok(());
-    }
-
-    let mut cargo =
-        process::Command::new(env::var_os("CARGO").unwrap_or_else(|| OsString::from("cargo")));
-    cargo.arg("build");
-
-    if let Some(triple) = settings.target_triple() {
-        cargo.arg(format!("--target={triple}"));
-    }
-
-    if let Some(features) = settings.features() {
-        cargo.arg(format!("--features={features}"));
-    }
-
-    match settings.build_artifact() {
-        bundle::BuildArtifact::Main => {}
-        bundle::BuildArtifact::Bin(name) => {
-            cargo.arg(format!("--bin={name}"));
-        }
-        bundle::BuildArtifact::Example(name) => {
-            cargo.arg(format!("--example={name}"));
-        }
-    }
-
-    match settings.build_profile() {
-        "dev" => {}
-        "release" => {
-            cargo.arg("--release");
-        }
-        custom => {
-            cargo.arg("--profile");
-            cargo.arg(custom);
-        }
-    }
-
-    if settings.all_features() {
-        cargo.arg("--all-features");
-    }
-
-    if settings.no_default_features() {
-        cargo.arg("--no-default-features");
-    }
-
-    let status = cargo.status()?;
-
-    if !status.success() {
-        return Err(Error::BuildFailed(status));
-    }
-
-    Ok(())
-}
+use std::path::PathBuf;
 
 fn run() -> Result<(), Error> {
-    let all_formats: Vec<&str> = Bundle::ALL.iter().map(Bundle::short_name).collect();
+    let mut all_formats: Vec<&str> = Bundle::ALL.iter().map(Bundle::short_name).collect();
+    all_formats.push(Bundle::ALL_FOR_TARGET);
 
     let m = App::new("cargo-bundle")
-        .version(format!("v{}", env!("CARGO_PKG_RUST_VERSION")).as_str())
+        .version(format!("v{}", env!("CARGO_PKG_VERSION")).as_str())
         .bin_name("cargo")
         .setting(AppSettings::GlobalVersion)
         .setting(AppSettings::SubcommandRequired)
@@ -82,82 +23,346 @@ fn run() -> Result<(), Error> {
                 .author("George Burton <burtonageo@gmail.com>")
                 .about("Bundle Rust executables into OS bundles")
                 .setting(AppSettings::DisableVersion)
+                .setting(AppSettings::DisableHelpSubcommand)
                 .setting(AppSettings::UnifiedHelpMessage)
+                .args(&settings_args(&all_formats))
                 .arg(
-                    Arg::with_name("bin")
-                        .long("bin")
-                        .value_name("NAME")
-                        .help("Bundle the specified binary"),
+                    Arg::with_name("no-build")
+                        .long("no-build")
+                        .help("Bundle the existing binary without running `cargo build`"),
                 )
                 .arg(
-                    Arg::with_name("example")
-                        .long("example")
-                        .value_name("NAME")
-                        .conflicts_with("bin")
-                        .help("Bundle the specified example"),
+                    Arg::with_name("binary")
+                        .long("binary")
+                        .value_name("[TRIPLE=]PATH")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help(
+                            "Bundle the binary at the given path instead of building it \
+                             (optionally only for the given target triple)",
+                        ),
                 )
-                .arg(
-                    Arg::with_name("format")
-                        .long("format")
-                        .value_name("FORMAT")
-                        .possible_values(&all_formats)
-                        .help("Which bundle format to produce"),
+                .subcommand(
+                    SubCommand::with_name("init")
+                        .about("Add a [package.metadata.bundle] section to Cargo.toml")
+                        .setting(AppSettings::UnifiedHelpMessage)
+                        .arg(
+                            Arg::with_name("package")
+                                .short("p")
+                                .long("package")
+                                .value_name("SPEC")
+                                .help("Package to add the section to"),
+                        )
+                        .arg(
+                            Arg::with_name("force")
+                                .long("force")
+                                .help("Replace an existing [package.metadata.bundle] section"),
+                        ),
                 )
-                .arg(
-                    Arg::with_name("release")
-                        .long("release")
-                        .help("Build a bundle from a target built in release mode"),
-                )
-                .arg(
-                    Arg::with_name("profile")
-                        .long("profile")
-                        .value_name("NAME")
-                        .conflicts_with("release")
-                        .help("Build a bundle from a target build using the given profile"),
+                .subcommand(
+                    SubCommand::with_name("schema")
+                        .about("Print a JSON Schema for the [package.metadata.bundle] section"),
                 )
-                .arg(
-                    Arg::with_name("target")
-                        .long("target")
-                        .value_name("TRIPLE")
-                        .help("Build a bundle for the target triple"),
-                )
-                .arg(
-                    Arg::with_name("features")
-                        .long("features")
-                        .value_name("FEATURES")
-                        .help("Set crate features for the bundle. Eg: `--features \"f1 f2\"`"),
-                )
-                .arg(
-                    Arg::with_name("all-features")
-                        .long("all-features")
-                        .help("Build a bundle with all crate features."),
-                )
-                .arg(
-                    Arg::with_name("no-default-features")
-                        .long("no-default-features")
-                        .help("Build a bundle without the default crate features."),
+                .subcommand(
+                    SubCommand::with_name("lint")
+                        .about("Check the bundle settings for problems without bundling")
+                        .setting(AppSettings::UnifiedHelpMessage)
+                        .args(&settings_args(&all_formats))
+                        .arg(
+                            Arg::with_name("deny")
+                                .long("deny")
+                                .short("D")
+                                .value_name("LEVEL")
+                                .possible_values(&["warnings"])
+                                .help("Fail if there are any warnings (`--deny warnings`)"),
+                        ),
                 ),
         )
         .get_matches();
 
     if let Some(m) = m.subcommand_matches("bundle") {
-        let output_paths = env::current_dir()
-            .map_err(From::from)
-            .and_then(|d| bundle::Settings::new(d, m))
-            .and_then(|s| {
-                build_project_if_unbuilt(&s)?;
-                Ok(s)
-            })
-            .and_then(bundle::run)?;
-
-        terminal::print_finished(&output_paths)?;
+        if let Some(m) = m.subcommand_matches("init") {
+            cargo_bundle::init(
+                &env::current_dir()?,
+                m.value_of("package"),
+                m.is_present("force"),
+            )?;
+            return Ok(());
+        }
+        if m.subcommand_matches("schema").is_some() {
+            let schema = cargo_bundle::metadata_schema();
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+            return Ok(());
+        }
+        if let Some(m) = m.subcommand_matches("lint") {
+            return lint(m);
+        }
+        set_message_format(m);
+        let settings =
+            settings_builder(env::current_dir()?, m)?.build_with(output::print_warning)?;
+        let mut bundler = Bundler::new(settings);
+        if m.is_present("no-build") || env::var("CARGO_BUNDLE_SKIP_BUILD").is_ok() {
+            bundler = bundler.no_build();
+        }
+        let report = bundler.run_with(print_bundle_event)?;
+        let success = report.failures.is_empty();
+        output::print_json_message("bundle-finished", json!({ "success": success }))?;
+        let output_paths: Vec<PathBuf> = report
+            .artifacts
+            .into_iter()
+            .map(|artifact| artifact.path)
+            .collect();
+        if success || !output_paths.is_empty() {
+            output::print_finished(&output_paths)?;
+        }
+        if !success {
+            let failed: Vec<String> = report
+                .failures
+                .iter()
+                .map(|failure| format!("{} ({})", failure.format.short_name(), failure.package))
+                .collect();
+            return Err(Error::BundlesFailed(failed.join(", ")));
+        }
     }
     Ok(())
 }
 
+/// Reports the progress of `cargo bundle` as the build starts and each bundle
+/// is started, created or fails: failures as errors on stderr, and everything as JSON messages
+/// (if they were requested).
+fn print_bundle_event(event: BundleEvent) -> Result<(), Error> {
+    match event {
+        BundleEvent::BuildStarted {
+            packages,
+            target,
+            profile,
+        } => output::print_json_message(
+            "build-started",
+            json!({
+                "packages": packages,
+                "target": target,
+                "profile": profile,
+            }),
+        ),
+        BundleEvent::Started {
+            package,
+            format,
+            target,
+        } => output::print_json_message(
+            "bundle-started",
+            json!({
+                "package": package,
+                "format": format.short_name(),
+                "target": target,
+            }),
+        ),
+        BundleEvent::Created(artifact) => {
+            output::print_json_message("bundle-artifact", serde_json::to_value(artifact)?)
+        }
+        BundleEvent::Failed(failure) => {
+            output::print_error(&failure.error)?;
+            output::print_json_message(
+                "bundle-failed",
+                json!({
+                    "package": failure.package,
+                    "format": failure.format.short_name(),
+                    "message": failure.error.to_string(),
+                }),
+            )
+        }
+    }
+}
+
+/// Runs `cargo bundle lint`, reporting the problems found in the settings of
+/// each selected package.
+fn lint(matches: &ArgMatches) -> Result<(), Error> {
+    set_message_format(matches);
+    let all_settings =
+        settings_builder(env::current_dir()?, matches)?.build_with(output::print_warning)?;
+    let (mut errors, mut warnings) = (0, 0);
+    for settings in &all_settings {
+        let diagnostics = cargo_bundle::lint(settings)?;
+        output::print_lint_report(settings.package_name(), &diagnostics)?;
+        for diagnostic in &diagnostics {
+            match diagnostic.severity {
+                Severity::Error => errors += 1,
+                Severity::Warning => warnings += 1,
+            }
+        }
+    }
+    if errors > 0 || (warnings > 0 && matches.value_of("deny") == Some("warnings")) {
+        return Err(Error::LintFailed(errors, warnings));
+    }
+    output::print_progress(
+        "Finished",
+        &format!("lint with {errors} error(s) and {warnings} warning(s)"),
+    )
+}
+
+fn set_message_format(matches: &ArgMatches) {
+    if let Some(format) = matches.value_of("message-format") {
+        let format = output::MessageFormat::from_short_name(format).unwrap();
+        output::set_message_format(format);
+    }
+}
+
+/// The command-line options that select the packages to bundle and their
+/// settings, shared by `cargo bundle` and `cargo bundle lint`.
+fn settings_args<'a>(all_formats: &'a [&'a str]) -> Vec<Arg<'a, 'a>> {
+    vec![
+        Arg::with_name("bin")
+            .long("bin")
+            .value_name("NAME")
+            .help("Bundle the specified binary"),
+        Arg::with_name("example")
+            .long("example")
+            .value_name("NAME")
+            .conflicts_with("bin")
+            .help("Bundle the specified example"),
+        Arg::with_name("package")
+            .short("p")
+            .long("package")
+            .value_name("SPEC")
+            .multiple(true)
+            .number_of_values(1)
+            .help("Package(s) to bundle"),
+        Arg::with_name("workspace")
+            .long("workspace")
+            .conflicts_with("package")
+            .help("Bundle all packages in the workspace that have bundle metadata"),
+        Arg::with_name("exclude")
+            .long("exclude")
+            .value_name("SPEC")
+            .multiple(true)
+            .number_of_values(1)
+            .requires("workspace")
+            .help("Exclude packages from the bundle"),
+        Arg::with_name("config")
+            .long("config")
+            .value_name("KEY=VALUE")
+            .multiple(true)
+            .number_of_values(1)
+            .help(
+                "Override a [package.metadata.bundle] setting, e.g. \
+                 `--config version=1.2.3-nightly` or `--config bin.foo.name=Foo`",
+            ),
+        Arg::with_name("variant")
+            .long("variant")
+            .value_name("NAME")
+            .help("Bundle the named variant, e.g. `--variant beta`"),
+        Arg::with_name("compression")
+            .long("compression")
+            .value_name("FORMAT")
+            .possible_values(DebCompression::ALL_NAMES)
+            .help("How to compress the archives in `deb` packages (default: gzip)"),
+        Arg::with_name("compression-level")
+            .long("compression-level")
+            .value_name("LEVEL")
+            .help("The level to compress the archives in `deb` packages at, for xz or zstd"),
+        Arg::with_name("format")
+            .long("format")
+            .value_name("FORMAT")
+            .possible_values(all_formats)
+            .multiple(true)
+            .require_delimiter(true)
+            .help(
+                "Which bundle formats to produce, as a comma-separated list \
+                 (may be repeated)",
+            ),
+        Arg::with_name("message-format")
+            .long("message-format")
+            .value_name("FMT")
+            .possible_values(&["human", "json"])
+            .default_value("human")
+            .help("Output format for messages: `human` or `json`"),
+        Arg::with_name("release")
+            .long("release")
+            .help("Build a bundle from a target built in release mode"),
+        Arg::with_name("profile")
+            .long("profile")
+            .value_name("NAME")
+            .conflicts_with("release")
+            .help("Build a bundle from a target build using the given profile"),
+        Arg::with_name("target")
+            .long("target")
+            .value_name("TRIPLE")
+            .help("Build a bundle for the target triple"),
+        Arg::with_name("features")
+            .long("features")
+            .value_name("FEATURES")
+            .help("Set crate features for the bundle. Eg: `--features \"f1 f2\"`"),
+        Arg::with_name("all-features")
+            .long("all-features")
+            .help("Build a bundle with all crate features."),
+        Arg::with_name("no-default-features")
+            .long("no-default-features")
+            .help("Build a bundle without the default crate features."),
+    ]
+}
+
+/// Translates the `cargo bundle` command-line options into a `SettingsBuilder`.
+fn settings_builder(current_dir: PathBuf, matches: &ArgMatches) -> Result<SettingsBuilder, Error> {
+    let mut builder = SettingsBuilder::new(current_dir);
+    for name in matches.values_of("package").into_iter().flatten() {
+        builder = builder.package(name);
+    }
+    if matches.is_present("workspace") {
+        builder = builder.workspace();
+    }
+    for name in matches.values_of("exclude").into_iter().flatten() {
+        builder = builder.exclude(name);
+    }
+    if let Some(bin) = matches.value_of("bin") {
+        builder = builder.artifact(BuildArtifact::Bin(bin.to_string()));
+    } else if let Some(example) = matches.value_of("example") {
+        builder = builder.artifact(BuildArtifact::Example(example.to_string()));
+    }
+    if matches.is_present("release") {
+        builder = builder.profile("release");
+    } else if let Some(profile) = matches.value_of("profile") {
+        builder = builder.profile(profile);
+    }
+    if let Some(triple) = matches.value_of("target") {
+        builder = builder.target(triple);
+    }
+    for name in matches.values_of("format").into_iter().flatten() {
+        if name == Bundle::ALL_FOR_TARGET {
+            builder = builder.all_formats_for_target();
+        } else {
+            builder = builder.format(Bundle::from_short_name(name)?);
+        }
+    }
+    if let Some(features) = matches.value_of("features") {
+        builder = builder.features(features);
+    }
+    if matches.is_present("all-features") {
+        builder = builder.all_features();
+    }
+    if matches.is_present("no-default-features") {
+        builder = builder.no_default_features();
+    }
+    for binary in matches.values_of("binary").into_iter().flatten() {
+        builder = builder.binary(binary);
+    }
+    if let Some(variant) = matches.value_of("variant") {
+        builder = builder.variant(variant);
+    }
+    builder = builder.config_from_env();
+    if let Some(compression) = matches.value_of("compression") {
+        builder = builder.config(format!("deb_compression={compression}"));
+    }
+    if let Some(level) = matches.value_of("compression-level") {
+        builder = builder.config(format!("deb_compression_level={level}"));
+    }
+    for config in matches.values_of("config").into_iter().flatten() {
+        builder = builder.config(config);
+    }
+    Ok(builder)
+}
+
 fn main() {
     if let Err(error) = run() {
-        terminal::print_error(&error).unwrap();
+        output::print_error(&error).unwrap();
         std::process::exit(1);
     }
 }
@@ -0,0 +1,211 @@
+// The messages printed by the `cargo bundle` command: progress, warnings and
+// errors on stderr, and (if requested) JSON messages on stdout.  The library's
+// bundlers print their own progress through its `terminal` module.
+
+use cargo_bundle::{Diagnostic, Error, Severity};
+
+use serde_json::Value;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static JSON_MESSAGES: AtomicBool = AtomicBool::new(false);
+
+/// How progress and results are reported, as selected by `--message-format`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MessageFormat {
+    /// Human-readable messages on stderr, and the finished bundle paths on
+    /// stdout.
+    Human,
+    /// Human-readable messages on stderr, and one JSON object per event on
+    /// stdout, like `cargo build --message-format json`.
+    Json,
+}
+
+impl MessageFormat {
+    pub fn from_short_name(name: &str) -> Option<MessageFormat> {
+        match name {
+            "human" => Some(MessageFormat::Human),
+            "json" => Some(MessageFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+pub fn set_message_format(format: MessageFormat) {
+    JSON_MESSAGES.store(format == MessageFormat::Json, Ordering::Relaxed);
+}
+
+pub fn message_format() -> MessageFormat {
+    if JSON_MESSAGES.load(Ordering::Relaxed) {
+        MessageFormat::Json
+    } else {
+        MessageFormat::Human
+    }
+}
+
+/// Prints a JSON message to stdout on a single line, if JSON messages were
+/// requested.  `reason` names the kind of event, as in cargo's messages.
+pub fn print_json_message(reason: &str, mut message: Value) -> Result<(), Error> {
+    if message_format() != MessageFormat::Json {
+        return Ok(());
+    }
+    if let Value::Object(ref mut fields) = message {
+        fields.insert("reason".to_string(), Value::from(reason));
+    }
+    let mut output = io::stdout().lock();
+    serde_json::to_writer(&mut output, &message)?;
+    writeln!(output)?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Prints a message to stderr, in the same format that `cargo` uses,
+/// indicating that we have finished the the given bundles.  The paths are
+/// printed to stdout, unless JSON messages were requested (in which case they
+/// have already been reported as `bundle-artifact` messages).
+pub fn print_finished(output_paths: &Vec<PathBuf>) -> Result<(), Error> {
+    let pluralised = if output_paths.len() == 1 {
+        "bundle"
+    } else {
+        "bundles"
+    };
+    let msg = format!("{} {} at:", output_paths.len(), pluralised);
+    print_progress("Finished", &msg)?;
+    if message_format() == MessageFormat::Json {
+        return Ok(());
+    }
+    for path in output_paths {
+        println!("        {}", path.display());
+    }
+    Ok(())
+}
+
+fn safe_term_attr<T: term::Terminal + ?Sized>(
+    output: &mut Box<T>,
+    attr: term::Attr,
+) -> term::Result<()> {
+    match output.supports_attr(attr) {
+        true => output.attr(attr),
+        false => Ok(()),
+    }
+}
+
+pub fn print_progress(step: &str, msg: &str) -> Result<(), Error> {
+    if let Some(mut output) = term::stderr() {
+        safe_term_attr(&mut output, term::Attr::Bold)?;
+        output.fg(term::color::GREEN)?;
+        write!(output, "    {step}")?;
+        output.reset()?;
+        writeln!(output, " {msg}")?;
+        output.flush()?;
+        Ok(())
+    } else {
+        let mut output = io::stderr();
+        write!(output, "    {step}")?;
+        writeln!(output, " {msg}")?;
+        output.flush()?;
+        Ok(())
+    }
+}
+
+/// Prints a warning message to stderr, in the same format that `cargo` uses.
+pub fn print_warning(message: &str) -> Result<(), Error> {
+    if let Some(mut output) = term::stderr() {
+        safe_term_attr(&mut output, term::Attr::Bold)?;
+        output.fg(term::color::YELLOW)?;
+        write!(output, "warning:")?;
+        output.reset()?;
+        writeln!(output, " {message}")?;
+        output.flush()?;
+        Ok(())
+    } else {
+        let mut output = io::stderr();
+        write!(output, "warning:")?;
+        writeln!(output, " {message}")?;
+        output.flush()?;
+        Ok(())
+    }
+}
+
+/// Prints an error to stderr, in the same format that `cargo` uses.
+pub fn print_error(error: &Error) -> Result<(), Error> {
+    if let Some(mut output) = term::stderr() {
+        safe_term_attr(&mut output, term::Attr::Bold)?;
+        output.fg(term::color::RED)?;
+        write!(output, "error:")?;
+        output.reset()?;
+
+        safe_term_attr(&mut output, term::Attr::Bold)?;
+        writeln!(output, " {error}")?;
+        output.reset()?;
+
+        output.flush()?;
+
+        Ok(())
+    } else {
+        let mut output = io::stderr();
+        write!(output, "error:")?;
+        writeln!(output, " {error}")?;
+
+        output.flush()?;
+        Ok(())
+    }
+}
+
+/// Prints the problems found by `cargo bundle lint` in a package's settings
+/// to stderr, grouped by the part of the settings they concern.  If JSON
+/// messages were requested, each problem is also reported as a
+/// `lint-diagnostic` message.
+pub fn print_lint_report(package: &str, diagnostics: &[Diagnostic]) -> Result<(), Error> {
+    print_progress("Checking", package)?;
+    let mut groups: Vec<&str> = Vec::new();
+    for diagnostic in diagnostics {
+        if !groups.contains(&diagnostic.group) {
+            groups.push(diagnostic.group);
+        }
+    }
+    for group in groups {
+        let mut output = io::stderr();
+        writeln!(output, "  {group}:")?;
+        for diagnostic in diagnostics.iter().filter(|d| d.group == group) {
+            print_diagnostic(diagnostic)?;
+            print_json_message(
+                "lint-diagnostic",
+                serde_json::json!({
+                    "package": package,
+                    "severity": diagnostic.severity,
+                    "group": diagnostic.group,
+                    "message": diagnostic.message,
+                }),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn print_diagnostic(diagnostic: &Diagnostic) -> Result<(), Error> {
+    let color = match diagnostic.severity {
+        Severity::Error => term::color::RED,
+        Severity::Warning => term::color::YELLOW,
+    };
+    if let Some(mut output) = term::stderr() {
+        write!(output, "    ")?;
+        safe_term_attr(&mut output, term::Attr::Bold)?;
+        output.fg(color)?;
+        write!(output, "{}:", diagnostic.severity)?;
+        output.reset()?;
+        writeln!(output, " {}", diagnostic.message)?;
+        output.flush()?;
+        Ok(())
+    } else {
+        let mut output = io::stderr();
+        writeln!(
+            output,
+            "    {}: {}",
+            diagnostic.severity, diagnostic.message
+        )?;
+        output.flush()?;
+        Ok(())
+    }
+}